@@ -0,0 +1,34 @@
+//! Runs networked games: waits for players to connect, then plays rounds
+//! between them, sending every client each tick's turns.
+
+use snake::net::DEFAULT_PORT;
+use snake::options::{Options, USAGE};
+use snake::server::Server;
+
+fn main() {
+  let options = match Options::from_args(std::env::args().skip(1)) {
+    Ok(options) => options,
+    Err(error) => {
+      eprintln!("{}\n{}", error, USAGE);
+      std::process::exit(2);
+    }
+  };
+
+  let port = options.port.unwrap_or(DEFAULT_PORT);
+  let players = options.players.unwrap_or(2);
+  let rules = options.rules.unwrap_or_default();
+  let mut server = match Server::bind(("0.0.0.0", port), players, options.seed, rules) {
+    Ok(server) => server,
+    Err(error) => {
+      eprintln!("Couldn't listen on port {}: {}", port, error);
+      std::process::exit(1);
+    }
+  };
+
+  println!(
+    "Listening on port {} for {} players",
+    port,
+    server.players()
+  );
+  server.run();
+}
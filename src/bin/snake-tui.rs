@@ -0,0 +1,597 @@
+//! Plays snake in a terminal, for when there's no window to open.
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+  self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, MouseButton,
+  MouseEventKind,
+};
+use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use snake::creature::CreatureKind;
+use snake::level::Level;
+use snake::menu::Menu;
+use snake::occupancy::Tile;
+use snake::options::{Options, USAGE};
+use snake::pellet::PelletKind;
+use snake::power_up::PowerUpKind;
+use snake::replay::Replay;
+use snake::rules::Rules;
+use snake::session::Session;
+use snake::simulation::Simulation;
+use snake::ticker::Ticker;
+use snake::turn_queue::TurnQueue;
+use snake::util::Direction;
+use std::io::{self, Stdout, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Longest to wait for a key before running the next frame.
+const FRAME_TIME: Duration = Duration::from_millis(5);
+
+/// Both ends of a portal share a colour, taken in turn from these.
+const PORTAL_COLORS: [Color; 9] = [
+  Color::DarkMagenta,
+  Color::DarkCyan,
+  Color::DarkYellow,
+  Color::DarkBlue,
+  Color::DarkRed,
+  Color::Magenta,
+  Color::Cyan,
+  Color::Yellow,
+  Color::Blue,
+];
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Screen {
+  MainMenu,
+  Playing,
+  GameOver,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum MenuAction {
+  Play,
+  Rules,
+  Level,
+  Quit,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Key {
+  Up,
+  Down,
+  Left,
+  Right,
+  Enter,
+  Escape,
+}
+
+/// Where the mouse pointer moved or clicked, as a terminal column and row.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Mouse {
+  Moved(u16, u16),
+  Clicked(u16, u16),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Input {
+  Key(Key),
+  Mouse(Mouse),
+  /// The terminal's new width and height.
+  Resized(u16, u16),
+}
+
+/// Puts the terminal into raw mode on an alternate screen and restores it
+/// when dropped, even if the game panics.
+struct Terminal {
+  stdout: Stdout,
+}
+
+impl Terminal {
+  fn new() -> io::Result<Self> {
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, Hide)?;
+
+    Ok(Self { stdout })
+  }
+}
+
+impl Drop for Terminal {
+  fn drop(&mut self) {
+    let _ = execute!(
+      self.stdout,
+      ResetColor,
+      Show,
+      DisableMouseCapture,
+      LeaveAlternateScreen
+    );
+    let _ = terminal::disable_raw_mode();
+  }
+}
+
+struct TuiGame {
+  screen: Screen,
+  quitting: bool,
+  /// Something changed since the last draw.
+  dirty: bool,
+  /// The screen shown by the last draw, to know when to clear the terminal.
+  drawn_screen: Option<Screen>,
+  menu: Menu<MenuAction>,
+  rule_sets: Vec<Rules>,
+  rules_index: usize,
+  levels: Vec<Level>,
+  /// 0 is the plain board, anything else is an index into `levels` plus one.
+  level_index: usize,
+  fixed_seed: Option<u64>,
+  session: Session,
+  record_path: Option<PathBuf>,
+  /// Shown under the menu or the game over line, since anything printed
+  /// would end up on the game's screen.
+  message: String,
+  /// The terminal is too small to show the whole board.
+  too_small: bool,
+  simulation: Simulation,
+  ticker: Ticker,
+  turns: TurnQueue,
+}
+
+impl TuiGame {
+  fn new(options: Options, playback: Option<Replay>) -> Self {
+    let session = Session::new(playback);
+    let mut rule_sets = Rules::available();
+    let rules_index = match options.rules {
+      Some(rules) => match rule_sets.iter().position(|other| *other == rules) {
+        Some(index) => index,
+        None => {
+          rule_sets.push(rules);
+          rule_sets.len() - 1
+        }
+      },
+      None => 0,
+    };
+
+    let mut game = Self {
+      screen: Screen::MainMenu,
+      quitting: false,
+      dirty: true,
+      drawn_screen: None,
+      menu: Menu::new((2.0, 3.0), 1.0, 40.0)
+        .item("Play", MenuAction::Play)
+        .item("Rules", MenuAction::Rules)
+        .item("Level", MenuAction::Level)
+        .item("Quit", MenuAction::Quit),
+      rule_sets,
+      rules_index,
+      levels: Level::available(),
+      level_index: 0,
+      fixed_seed: options.seed,
+      simulation: session.recording.simulation(),
+      session,
+      record_path: options.record,
+      message: String::new(),
+      too_small: false,
+      ticker: Ticker::new(),
+      turns: TurnQueue::new(),
+    };
+
+    game.update_menu();
+    game
+  }
+
+  fn level(&self) -> Option<&Level> {
+    match self.level_index {
+      0 => None,
+      index => Some(&self.levels[index - 1]),
+    }
+  }
+
+  fn level_name(&self) -> &str {
+    self.level().map_or("Open", |level| &level.name)
+  }
+
+  fn key_pressed(&mut self, key: Key) {
+    self.dirty = true;
+
+    match self.screen {
+      Screen::MainMenu => self.menu_key(key),
+      Screen::Playing => match key {
+        Key::Up => self.turns.push(Direction::Up),
+        Key::Down => self.turns.push(Direction::Down),
+        Key::Left => self.turns.push(Direction::Left),
+        Key::Right => self.turns.push(Direction::Right),
+        Key::Escape => self.screen = Screen::MainMenu,
+        Key::Enter => {}
+      },
+      Screen::GameOver => match key {
+        Key::Enter => self.screen = Screen::MainMenu,
+        Key::Escape => self.quitting = true,
+        _ => {}
+      },
+    }
+  }
+
+  fn menu_key(&mut self, key: Key) {
+    match key {
+      Key::Up => {
+        self.menu.focus_previous();
+      }
+      Key::Down => {
+        self.menu.focus_next();
+      }
+      Key::Escape => self.quitting = true,
+      Key::Enter | Key::Left | Key::Right => {
+        let step = if key == Key::Left { -1 } else { 1 };
+        if let Some(action) = self.menu.focused() {
+          self.choose(action, key == Key::Enter, step);
+        }
+      }
+    }
+  }
+
+  fn mouse_input(&mut self, mouse: Mouse) {
+    if self.screen != Screen::MainMenu {
+      return;
+    }
+
+    match mouse {
+      Mouse::Moved(column, row) => {
+        if self.menu.hover((column as f32, row as f32)) {
+          self.dirty = true;
+        }
+      }
+      Mouse::Clicked(column, row) => {
+        if let Some(action) = self.menu.click((column as f32, row as f32)) {
+          self.dirty = true;
+          self.choose(action, true, 1);
+        }
+      }
+    }
+  }
+
+  /// Acts on a menu item. Items with a setting step through its choices by
+  /// `step`; the others only act when `confirmed`.
+  fn choose(&mut self, action: MenuAction, confirmed: bool, step: isize) {
+    match action {
+      MenuAction::Play if confirmed => self.start_game(),
+      MenuAction::Rules => self.rules_index = cycle(self.rules_index, self.rule_sets.len(), step),
+      MenuAction::Level => self.level_index = cycle(self.level_index, self.levels.len() + 1, step),
+      MenuAction::Quit if confirmed => self.quitting = true,
+      _ => {}
+    }
+    self.update_menu();
+  }
+
+  /// Shows the current choices in the menu's labels.
+  fn update_menu(&mut self) {
+    let rules = format!("Rules: {}", self.rule_sets[self.rules_index].name);
+    let level = format!("Level: {}", self.level_name());
+    self.menu.set_label(MenuAction::Rules, rules);
+    self.menu.set_label(MenuAction::Level, level);
+  }
+
+  fn start_game(&mut self) {
+    let rules = self.rule_sets[self.rules_index].clone();
+    let level = self.level().cloned();
+    let simulation = self
+      .session
+      .start(self.fixed_seed, &rules, level.as_ref(), 1);
+
+    let (columns, rows) = board_size(&simulation);
+    if let Ok((width, height)) = terminal::size() {
+      if width < columns || height < rows {
+        self.message = format!(
+          "This board needs a terminal of at least {} by {}, not {} by {}",
+          columns, rows, width, height
+        );
+        return;
+      }
+    }
+
+    self.simulation = simulation;
+    self.message.clear();
+    self.too_small = false;
+    self.turns.clear();
+    self.ticker.reset();
+    self.screen = Screen::Playing;
+  }
+
+  /// Notes whether the board still fits after the terminal changed size.
+  /// The game waits while it doesn't.
+  fn resized(&mut self, width: u16, height: u16) {
+    let (columns, rows) = board_size(&self.simulation);
+    self.too_small = width < columns || height < rows;
+    self.drawn_screen = None;
+    self.dirty = true;
+  }
+
+  fn update(&mut self) {
+    if self.screen != Screen::Playing {
+      return;
+    }
+    if self.too_small {
+      self.ticker.reset();
+      return;
+    }
+
+    let ticks = self.ticker.update(self.simulation.tick_rate());
+    for _ in 0..ticks {
+      self.dirty = true;
+
+      let turns = &mut self.turns;
+      self.session.step(&mut self.simulation, |simulation| {
+        turns.next_turn(simulation.snakes[0].travelled)
+      });
+
+      if self.session.finished(&self.simulation) {
+        self.game_over();
+        break;
+      }
+    }
+  }
+
+  fn game_over(&mut self) {
+    self.screen = Screen::GameOver;
+
+    if let Some(path) = &self.record_path {
+      if let Err(error) = self.session.save_recording(&self.simulation, path) {
+        self.message = format!("Couldn't save replay to {}: {}", path.display(), error);
+      }
+    }
+  }
+
+  fn draw(&mut self, stdout: &mut Stdout) -> io::Result<()> {
+    if !self.dirty {
+      return Ok(());
+    }
+    self.dirty = false;
+
+    if self.drawn_screen != Some(self.screen) {
+      self.drawn_screen = Some(self.screen);
+      queue!(stdout, Clear(ClearType::All))?;
+    }
+
+    match self.screen {
+      Screen::MainMenu => self.draw_menu(stdout)?,
+      Screen::Playing | Screen::GameOver if self.too_small => {
+        let (columns, rows) = board_size(&self.simulation);
+        let text = format!(
+          "Make the terminal at least {} by {} to see the board",
+          columns, rows
+        );
+        queue!(stdout, MoveTo(0, 0), ResetColor, Print(text))?;
+      }
+      Screen::Playing => self.draw_board(stdout)?,
+      Screen::GameOver => {
+        self.draw_board(stdout)?;
+        let result = if self.simulation.won {
+          "You Win"
+        } else {
+          "Game Over"
+        };
+        let text = format!(
+          "{}  Seed: {}  (Enter for menu, Esc to quit)",
+          result, self.simulation.seed
+        );
+        let row = self.simulation.board.height as u16 + 2;
+        queue!(stdout, MoveTo(0, row), ResetColor, Print(text))?;
+        if !self.message.is_empty() {
+          queue!(
+            stdout,
+            MoveTo(0, row + 1),
+            SetForegroundColor(Color::Red),
+            Print(&self.message)
+          )?;
+        }
+      }
+    }
+
+    stdout.flush()
+  }
+
+  fn draw_menu(&self, stdout: &mut Stdout) -> io::Result<()> {
+    queue!(
+      stdout,
+      MoveTo(2, 1),
+      SetForegroundColor(Color::Green),
+      Print("SNAKE")
+    )?;
+
+    let items = self.menu.items();
+    for (i, item) in items.iter().enumerate() {
+      let (marker, color) = if self.menu.focus_index() == Some(i) {
+        ("> ", Color::Yellow)
+      } else {
+        ("  ", Color::White)
+      };
+      let (column, row) = self.menu.position(i);
+      queue!(
+        stdout,
+        MoveTo(column as u16, row as u16),
+        SetForegroundColor(color),
+        Print(marker),
+        Print(&item.label),
+        Clear(ClearType::UntilNewLine)
+      )?;
+    }
+
+    let (column, row) = self.menu.position(items.len() + 1);
+    queue!(
+      stdout,
+      MoveTo(column as u16, row as u16),
+      SetForegroundColor(Color::DarkGrey),
+      Print("Arrows or WASD to move, Enter or click to choose, Esc to quit"),
+      MoveTo(column as u16, row as u16 + 2),
+      SetForegroundColor(Color::Red),
+      Print(&self.message),
+      Clear(ClearType::UntilNewLine)
+    )?;
+
+    Ok(())
+  }
+
+  fn draw_board(&self, stdout: &mut Stdout) -> io::Result<()> {
+    let simulation = &self.simulation;
+    let board = simulation.board;
+    let snake = &simulation.snakes[0];
+    let head = snake.head();
+
+    let mut status = format!("Score: {}", snake.score);
+    if let Some(left) = simulation.pellets_left() {
+      status.push_str(&format!("   Pellets left: {}", left));
+    }
+    for effect in snake.effects.iter() {
+      let seconds = effect.until.saturating_sub(simulation.tick) as f32 / simulation.tick_rate();
+      status.push_str(&format!("   {} {:.1}s", effect.kind.name(), seconds));
+    }
+    queue!(
+      stdout,
+      MoveTo(0, 0),
+      Clear(ClearType::CurrentLine),
+      SetForegroundColor(Color::White),
+      Print(status)
+    )?;
+
+    for y in (0..board.height).rev() {
+      let row = (board.height - 1 - y) as u16 + 1;
+      queue!(stdout, MoveTo(0, row))?;
+
+      for x in 0..board.width {
+        let cell = (x, y);
+        let (text, color) = match simulation.occupancy.get(cell) {
+          Tile::Empty => ("  ", Color::Reset),
+          Tile::Wall => ("██", Color::DarkGrey),
+          Tile::Snake if cell == head => ("██", Color::Green),
+          Tile::Snake => ("▓▓", Color::DarkGreen),
+          Tile::Pellet => match simulation.pellet_at(cell).map(|pellet| pellet.kind) {
+            Some(PelletKind::Normal) | None => ("()", Color::Red),
+            Some(PelletKind::Bonus) => ("<>", Color::Yellow),
+            Some(PelletKind::Poison) => ("><", Color::Magenta),
+            Some(PelletKind::Shrink) => ("--", Color::Cyan),
+            Some(PelletKind::Speed) => (">>", Color::Blue),
+          },
+          Tile::Creature => match simulation.creature_at(cell).map(|creature| creature.kind) {
+            Some(CreatureKind::Mouse) => ("::", Color::White),
+            Some(CreatureKind::Chaser) => ("XX", Color::DarkRed),
+            Some(CreatureKind::Patrol) | None => ("▒▒", Color::Grey),
+          },
+          Tile::Portal => {
+            let pair = simulation
+              .portals
+              .iter()
+              .position(|(a, b)| *a == cell || *b == cell)
+              .unwrap_or(0);
+            ("@@", PORTAL_COLORS[pair % PORTAL_COLORS.len()])
+          }
+          Tile::PowerUp => match simulation.power_up.map(|power_up| power_up.kind) {
+            Some(PowerUpKind::Ghost) => ("[]", Color::White),
+            Some(PowerUpKind::Slow) => ("[]", Color::Blue),
+            Some(PowerUpKind::Magnet) => ("[]", Color::Red),
+            Some(PowerUpKind::Shield) | None => ("[]", Color::Yellow),
+          },
+        };
+        queue!(stdout, SetForegroundColor(color), Print(text))?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// The terminal columns and rows needed to show `simulation`'s board, with
+/// the score above it and the game over line below.
+fn board_size(simulation: &Simulation) -> (u16, u16) {
+  let board = simulation.board;
+  (board.width as u16 * 2, board.height as u16 + 3)
+}
+
+fn cycle(index: usize, len: usize, step: isize) -> usize {
+  (index as isize + step).rem_euclid(len as isize) as usize
+}
+
+fn read_input() -> io::Result<Option<Input>> {
+  let key = match event::read()? {
+    Event::Key(key) if key.kind != KeyEventKind::Release => key,
+    Event::Resize(width, height) => return Ok(Some(Input::Resized(width, height))),
+    Event::Mouse(mouse) => {
+      let mouse = match mouse.kind {
+        MouseEventKind::Moved => Mouse::Moved(mouse.column, mouse.row),
+        MouseEventKind::Down(MouseButton::Left) => Mouse::Clicked(mouse.column, mouse.row),
+        _ => return Ok(None),
+      };
+      return Ok(Some(Input::Mouse(mouse)));
+    }
+    _ => return Ok(None),
+  };
+
+  let key = match key.code {
+    KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('W') => Key::Up,
+    KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('S') => Key::Down,
+    KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('A') => Key::Left,
+    KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('D') => Key::Right,
+    KeyCode::Enter | KeyCode::Char(' ') => Key::Enter,
+    KeyCode::Esc | KeyCode::Char('q') => Key::Escape,
+    _ => return Ok(None),
+  };
+
+  Ok(Some(Input::Key(key)))
+}
+
+fn run(game: &mut TuiGame) -> io::Result<()> {
+  let mut terminal = Terminal::new()?;
+
+  while !game.quitting {
+    let mut timeout = FRAME_TIME;
+    while event::poll(timeout)? {
+      match read_input()? {
+        Some(Input::Key(key)) => game.key_pressed(key),
+        Some(Input::Mouse(mouse)) => game.mouse_input(mouse),
+        Some(Input::Resized(width, height)) => game.resized(width, height),
+        None => {}
+      }
+      timeout = Duration::ZERO;
+    }
+
+    game.update();
+    game.draw(&mut terminal.stdout)?;
+  }
+
+  Ok(())
+}
+
+fn main() {
+  let options = match Options::from_args(std::env::args().skip(1)) {
+    Ok(options) => options,
+    Err(error) => {
+      eprintln!("{}\n{}", error, USAGE);
+      std::process::exit(2);
+    }
+  };
+
+  let playback = options
+    .replay
+    .as_ref()
+    .map(|path| match Replay::load(path) {
+      Ok(replay) => replay,
+      Err(error) => {
+        eprintln!("{}: {}", path.display(), error);
+        std::process::exit(1);
+      }
+    });
+
+  if let (true, Some(replay)) = (options.verify, &playback) {
+    let simulation = replay.run();
+    println!(
+      "score: {} ticks: {}",
+      simulation.snakes[0].score, simulation.tick
+    );
+    return;
+  }
+
+  let mut game = TuiGame::new(options, playback);
+  if let Err(error) = run(&mut game) {
+    eprintln!("{}", error);
+    std::process::exit(1);
+  }
+}
@@ -0,0 +1,244 @@
+use crate::file::{self, FileError};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Keys bound to each action, at most this many.
+pub const SLOTS: usize = 2;
+
+/// Names of the keys that can be bound, as written in the controls file.
+pub const KEY_NAMES: &[&str] = &[
+  "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S",
+  "T", "U", "V", "W", "X", "Y", "Z", "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "Up",
+  "Down", "Left", "Right", "Return", "Escape", "Space", "Tab", "Back",
+];
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Action {
+  Up,
+  Down,
+  Left,
+  Right,
+  Confirm,
+  Back,
+  Pause,
+}
+
+impl Action {
+  pub const ALL: [Action; 7] = [
+    Action::Up,
+    Action::Down,
+    Action::Left,
+    Action::Right,
+    Action::Confirm,
+    Action::Back,
+    Action::Pause,
+  ];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Action::Up => "Up",
+      Action::Down => "Down",
+      Action::Left => "Left",
+      Action::Right => "Right",
+      Action::Confirm => "Confirm",
+      Action::Back => "Back",
+      Action::Pause => "Pause",
+    }
+  }
+}
+
+/// Which keys trigger each action, by key name.
+///
+/// An action can have a key in each of `SLOTS` slots. In versus games the
+/// first slot's direction keys steer player one and the second's player two.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bindings {
+  up: Vec<String>,
+  down: Vec<String>,
+  left: Vec<String>,
+  right: Vec<String>,
+  confirm: Vec<String>,
+  back: Vec<String>,
+  pause: Vec<String>,
+}
+
+impl Default for Bindings {
+  fn default() -> Self {
+    let keys = |names: &[&str]| names.iter().map(|name| String::from(*name)).collect();
+
+    Self {
+      up: keys(&["W", "Up"]),
+      down: keys(&["S", "Down"]),
+      left: keys(&["A", "Left"]),
+      right: keys(&["D", "Right"]),
+      confirm: keys(&["Return"]),
+      back: keys(&["Escape"]),
+      pause: keys(&["P"]),
+    }
+  }
+}
+
+impl Bindings {
+  /// Where the bindings live in the user's config directory.
+  pub fn default_path() -> Option<PathBuf> {
+    file::user_path(dirs::config_dir(), "controls.toml")
+  }
+
+  /// Loads the bindings from `path`. A missing file gives the defaults.
+  pub fn load(path: &Path) -> Result<Bindings, FileError> {
+    Ok(file::load(path, Bindings::parse)?.unwrap_or_default())
+  }
+
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    file::save(self, path)
+  }
+
+  /// Parses bindings, checking every key is known and used only once.
+  pub fn parse(text: &str) -> Result<Bindings, FileError> {
+    let bindings: Bindings = toml::from_str(text)?;
+    let invalid = |message: String| Err(FileError::Invalid(message));
+
+    let mut seen: Vec<(&str, Action)> = Vec::new();
+    for action in Action::ALL.iter() {
+      let keys = bindings.keys(*action);
+      if keys.is_empty() || keys.len() > SLOTS {
+        return invalid(format!("{} needs 1 to {} keys", action.name(), SLOTS));
+      }
+
+      for key in keys {
+        if !KEY_NAMES.contains(&key.as_str()) {
+          return invalid(format!("unknown key: {}", key));
+        }
+        if let Some((_, other)) = seen.iter().find(|(name, _)| name == key) {
+          return invalid(format!(
+            "{} is bound to both {} and {}",
+            key,
+            other.name(),
+            action.name()
+          ));
+        }
+        seen.push((key, *action));
+      }
+    }
+
+    Ok(bindings)
+  }
+
+  pub fn keys(&self, action: Action) -> &[String] {
+    match action {
+      Action::Up => &self.up,
+      Action::Down => &self.down,
+      Action::Left => &self.left,
+      Action::Right => &self.right,
+      Action::Confirm => &self.confirm,
+      Action::Back => &self.back,
+      Action::Pause => &self.pause,
+    }
+  }
+
+  fn keys_mut(&mut self, action: Action) -> &mut Vec<String> {
+    match action {
+      Action::Up => &mut self.up,
+      Action::Down => &mut self.down,
+      Action::Left => &mut self.left,
+      Action::Right => &mut self.right,
+      Action::Confirm => &mut self.confirm,
+      Action::Back => &mut self.back,
+      Action::Pause => &mut self.pause,
+    }
+  }
+
+  /// The actions bound to the key called `name`, with the slot of each.
+  pub fn actions(&self, name: &str) -> Vec<(Action, usize)> {
+    let mut actions = Vec::new();
+    for action in Action::ALL.iter() {
+      if let Some(slot) = self.keys(*action).iter().position(|key| key == name) {
+        actions.push((*action, slot));
+      }
+    }
+    actions
+  }
+
+  /// Binds the key called `name` to `action` in `slot`, replacing what was
+  /// there. If another action or slot already uses the key nothing changes
+  /// and that action is returned.
+  pub fn bind(&mut self, action: Action, slot: usize, name: &str) -> Result<(), Action> {
+    let conflict = self
+      .actions(name)
+      .into_iter()
+      .find(|(other, other_slot)| (*other, *other_slot) != (action, slot));
+    if let Some((other, _)) = conflict {
+      return Err(other);
+    }
+
+    let keys = self.keys_mut(action);
+    if slot < keys.len() {
+      keys[slot] = String::from(name);
+    } else if slot < SLOTS {
+      keys.push(String::from(name));
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn the_defaults_read_back_the_same() {
+    let text = toml::to_string(&Bindings::default()).unwrap();
+    assert_eq!(Bindings::parse(&text).unwrap(), Bindings::default());
+  }
+
+  #[test]
+  fn a_key_bound_twice_is_turned_away() {
+    let text = "up = [\"W\"]\ndown = [\"W\"]";
+    match Bindings::parse(text) {
+      Err(FileError::Invalid(message)) => {
+        assert_eq!(message, "W is bound to both Up and Down")
+      }
+      result => panic!("expected a conflict, got {:?}", result),
+    }
+  }
+
+  #[test]
+  fn unknown_keys_and_wrong_key_counts_are_turned_away() {
+    for text in [
+      "up = [\"Banana\"]",
+      "up = []",
+      "up = [\"W\", \"Up\", \"I\"]",
+    ]
+    .iter()
+    {
+      assert!(
+        matches!(Bindings::parse(text), Err(FileError::Invalid(_))),
+        "{}",
+        text
+      );
+    }
+  }
+
+  #[test]
+  fn binding_a_free_key_replaces_the_slot() {
+    let mut bindings = Bindings::default();
+    assert_eq!(bindings.bind(Action::Up, 0, "I"), Ok(()));
+    assert_eq!(bindings.keys(Action::Up), ["I", "Up"]);
+    assert_eq!(bindings.actions("I"), vec![(Action::Up, 0)]);
+    assert!(bindings.actions("W").is_empty());
+  }
+
+  #[test]
+  fn binding_a_key_in_use_changes_nothing() {
+    let mut bindings = Bindings::default();
+    assert_eq!(bindings.bind(Action::Up, 0, "S"), Err(Action::Down));
+    assert_eq!(bindings.bind(Action::Up, 0, "Up"), Err(Action::Up));
+    assert_eq!(bindings, Bindings::default());
+
+    assert_eq!(bindings.bind(Action::Up, 0, "W"), Ok(()));
+    assert_eq!(bindings.bind(Action::Pause, 1, "Space"), Ok(()));
+    assert_eq!(bindings.keys(Action::Pause), ["P", "Space"]);
+  }
+}
@@ -0,0 +1,45 @@
+pub type Cell = (i32, i32);
+
+/// The playing field, `width` by `height` cells with `(0, 0)` in the
+/// bottom-left corner.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Board {
+  pub width: i32,
+  pub height: i32,
+}
+
+impl Board {
+  pub fn new(width: i32, height: i32) -> Self {
+    Self { width, height }
+  }
+
+  pub fn contains(&self, cell: Cell) -> bool {
+    cell.0 >= 0 && cell.0 < self.width && cell.1 >= 0 && cell.1 < self.height
+  }
+
+  /// `cell` moved back onto the board from whichever edge it went over.
+  pub fn wrap(&self, cell: Cell) -> Cell {
+    (
+      cell.0.rem_euclid(self.width),
+      cell.1.rem_euclid(self.height),
+    )
+  }
+
+  pub fn center(&self) -> Cell {
+    (self.width / 2, self.height / 2)
+  }
+
+  /// The ring of cells along the edge of the board.
+  pub fn border(&self) -> Vec<Cell> {
+    let mut cells = Vec::new();
+    for x in 0..self.width {
+      cells.push((x, 0));
+      cells.push((x, self.height - 1));
+    }
+    for y in 1..self.height - 1 {
+      cells.push((0, y));
+      cells.push((self.width - 1, y));
+    }
+    cells
+  }
+}
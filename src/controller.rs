@@ -0,0 +1,324 @@
+use crate::board::Cell;
+use crate::occupancy::Tile;
+use crate::rules::Rules;
+use crate::simulation::Simulation;
+use crate::util::Direction;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+const DIRECTIONS: [Direction; 4] = [
+  Direction::Up,
+  Direction::Down,
+  Direction::Left,
+  Direction::Right,
+];
+
+/// Something that steers the snake in place of a player.
+pub trait Controller {
+  fn name(&self) -> &'static str;
+
+  /// The direction to take this tick, or `None` to keep going.
+  fn next_turn(&mut self, simulation: &Simulation) -> Option<Direction>;
+}
+
+/// Names accepted by `bot`, in menu order.
+pub const BOT_NAMES: [&str; 3] = ["Greedy", "Shortest Path", "Hamiltonian"];
+
+/// The built-in bot called `name`, ignoring case.
+pub fn bot(name: &str) -> Option<Box<dyn Controller>> {
+  match name.to_lowercase().as_str() {
+    "greedy" => Some(Box::new(Greedy)),
+    "shortest path" | "shortest-path" | "bfs" => Some(Box::new(ShortestPath)),
+    "hamiltonian" => Some(Box::new(Hamiltonian::new())),
+    _ => None,
+  }
+}
+
+/// Heads straight for the nearest pellet, only avoiding what's directly in
+/// front of it.
+pub struct Greedy;
+
+impl Controller for Greedy {
+  fn name(&self) -> &'static str {
+    "Greedy"
+  }
+
+  fn next_turn(&mut self, simulation: &Simulation) -> Option<Direction> {
+    safe_moves(simulation)
+      .into_iter()
+      .min_by_key(|(_, cell)| {
+        simulation
+          .pellets
+          .iter()
+          .map(|pellet| distance(*cell, pellet.cell))
+          .min()
+      })
+      .map(|(direction, _)| direction)
+      .or_else(|| fallback(simulation))
+  }
+}
+
+/// Follows the shortest safe path to the nearest pellet. With no path it
+/// moves to wherever leaves the most room.
+pub struct ShortestPath;
+
+impl Controller for ShortestPath {
+  fn name(&self) -> &'static str {
+    "Shortest Path"
+  }
+
+  fn next_turn(&mut self, simulation: &Simulation) -> Option<Direction> {
+    path_to_pellet(simulation).or_else(|| roomiest_move(simulation))
+  }
+}
+
+/// Walks a fixed cycle through every free cell, so it can never trap itself
+/// and always fills the board eventually. On boards without such a cycle it
+/// plays like `ShortestPath`.
+pub struct Hamiltonian {
+  cycle: Option<HashMap<Cell, Direction>>,
+  built: bool,
+}
+
+impl Default for Hamiltonian {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Hamiltonian {
+  pub fn new() -> Self {
+    Self {
+      cycle: None,
+      built: false,
+    }
+  }
+}
+
+impl Controller for Hamiltonian {
+  fn name(&self) -> &'static str {
+    "Hamiltonian"
+  }
+
+  fn next_turn(&mut self, simulation: &Simulation) -> Option<Direction> {
+    if !self.built || simulation.tick == 0 {
+      self.cycle = hamiltonian_cycle(simulation);
+      self.built = true;
+    }
+
+    let head = simulation.snakes[0].head();
+    match self.cycle.as_ref().and_then(|cycle| cycle.get(&head)) {
+      Some(direction) => Some(*direction),
+      None => ShortestPath.next_turn(simulation),
+    }
+  }
+}
+
+/// Totals from playing a bot through a number of games.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Benchmark {
+  pub games: u32,
+  pub total_score: u64,
+  pub total_ticks: u64,
+  pub wins: u32,
+}
+
+impl Benchmark {
+  pub fn average_score(&self) -> f64 {
+    self.total_score as f64 / self.games.max(1) as f64
+  }
+
+  pub fn average_ticks(&self) -> f64 {
+    self.total_ticks as f64 / self.games.max(1) as f64
+  }
+}
+
+/// Plays `games` games under `rules` without rendering, seeded `seed`,
+/// `seed + 1` and so on. A game that outlasts the square of the board's area
+/// in ticks is stopped where it is.
+pub fn benchmark(
+  controller: &mut dyn Controller,
+  rules: &Rules,
+  games: u32,
+  seed: u64,
+) -> Benchmark {
+  let area = (rules.board_width * rules.board_height) as u64;
+  let tick_limit = area * area;
+  let mut result = Benchmark::default();
+
+  for game in 0..games {
+    let mut simulation = Simulation::new(rules.clone(), seed.wrapping_add(game as u64));
+    while !simulation.game_over() && simulation.tick < tick_limit {
+      let turns: Vec<Direction> = controller.next_turn(&simulation).into_iter().collect();
+      simulation.step(&turns);
+    }
+
+    result.games += 1;
+    result.total_score += simulation.snakes[0].score as u64;
+    result.total_ticks += simulation.tick;
+    if simulation.won {
+      result.wins += 1;
+    }
+  }
+
+  result
+}
+
+fn distance(a: Cell, b: Cell) -> i32 {
+  (a.0 - b.0).abs() + (a.1 - b.1).abs()
+}
+
+/// Moves that don't reverse the snake or run it into something.
+fn safe_moves(simulation: &Simulation) -> Vec<(Direction, Cell)> {
+  let head = simulation.snakes[0].head();
+  let travelled = simulation.snakes[0].travelled;
+
+  DIRECTIONS
+    .iter()
+    .filter(|direction| **direction != travelled.opposite())
+    .map(|direction| (*direction, simulation.neighbour(head, *direction)))
+    .filter(|(_, cell)| simulation.is_safe(*cell))
+    .collect()
+}
+
+/// Keeps going if nothing is safe, there's no way out anyway.
+fn fallback(simulation: &Simulation) -> Option<Direction> {
+  match simulation.snakes[0].travelled {
+    Direction::None => Some(Direction::Up),
+    _ => None,
+  }
+}
+
+/// The first step of the shortest safe path from the head to the nearest
+/// pellet.
+fn path_to_pellet(simulation: &Simulation) -> Option<Direction> {
+  let mut first_steps: HashMap<Cell, Direction> = HashMap::new();
+  let mut queue = VecDeque::new();
+
+  for (direction, cell) in safe_moves(simulation) {
+    first_steps.insert(cell, direction);
+    queue.push_back(cell);
+  }
+
+  while let Some(cell) = queue.pop_front() {
+    let first_step = first_steps[&cell];
+    if simulation.occupancy.get(cell) == Tile::Pellet {
+      return Some(first_step);
+    }
+
+    for direction in DIRECTIONS.iter() {
+      let next = simulation.neighbour(cell, *direction);
+      if simulation.is_safe(next) && !first_steps.contains_key(&next) {
+        first_steps.insert(next, first_step);
+        queue.push_back(next);
+      }
+    }
+  }
+
+  None
+}
+
+/// The safe move with the most free cells reachable after it.
+fn roomiest_move(simulation: &Simulation) -> Option<Direction> {
+  safe_moves(simulation)
+    .into_iter()
+    .max_by_key(|(_, cell)| reachable_cells(simulation, *cell))
+    .map(|(direction, _)| direction)
+    .or_else(|| fallback(simulation))
+}
+
+fn reachable_cells(simulation: &Simulation, start: Cell) -> usize {
+  let mut seen = HashSet::new();
+  seen.insert(start);
+  let mut queue = VecDeque::from(vec![start]);
+
+  while let Some(cell) = queue.pop_front() {
+    for direction in DIRECTIONS.iter() {
+      let next = simulation.neighbour(cell, *direction);
+      if simulation.is_safe(next) && seen.insert(next) {
+        queue.push_back(next);
+      }
+    }
+  }
+
+  seen.len()
+}
+
+/// A cycle through every cell that isn't a wall or portal, as the direction
+/// to leave each cell by. Only rectangles with an even side are supported.
+fn hamiltonian_cycle(simulation: &Simulation) -> Option<HashMap<Cell, Direction>> {
+  let board = simulation.board;
+  let open: Vec<Cell> = (0..board.height)
+    .flat_map(|y| (0..board.width).map(move |x| (x, y)))
+    .filter(|cell| !matches!(simulation.occupancy.get(*cell), Tile::Wall | Tile::Portal))
+    .collect();
+
+  let min_x = open.iter().map(|cell| cell.0).min()?;
+  let max_x = open.iter().map(|cell| cell.0).max()?;
+  let min_y = open.iter().map(|cell| cell.1).min()?;
+  let max_y = open.iter().map(|cell| cell.1).max()?;
+  let width = max_x - min_x + 1;
+  let height = max_y - min_y + 1;
+
+  if open.len() != (width * height) as usize || width < 2 || height < 2 {
+    return None;
+  }
+
+  let cycle = if height % 2 == 0 {
+    rows_cycle(width, height)
+  } else if width % 2 == 0 {
+    rows_cycle(height, width)
+      .into_iter()
+      .map(|((x, y), direction)| ((y, x), transpose(direction)))
+      .collect()
+  } else {
+    return None;
+  };
+
+  Some(
+    cycle
+      .into_iter()
+      .map(|((x, y), direction)| ((x + min_x, y + min_y), direction))
+      .collect(),
+  )
+}
+
+/// Boustrophedon through rows `0..height` of columns `1..width`, returning
+/// down column 0. `height` must be even.
+fn rows_cycle(width: i32, height: i32) -> HashMap<Cell, Direction> {
+  let mut cycle = HashMap::new();
+
+  for y in 0..height {
+    let rightward = y % 2 == 0;
+    for x in 1..width {
+      let direction = if rightward && x < width - 1 {
+        Direction::Right
+      } else if !rightward && x > 1 {
+        Direction::Left
+      } else if y < height - 1 {
+        Direction::Up
+      } else {
+        Direction::Left
+      };
+      cycle.insert((x, y), direction);
+    }
+
+    let direction = if y == 0 {
+      Direction::Right
+    } else {
+      Direction::Down
+    };
+    cycle.insert((0, y), direction);
+  }
+
+  cycle
+}
+
+fn transpose(direction: Direction) -> Direction {
+  match direction {
+    Direction::Up => Direction::Right,
+    Direction::Right => Direction::Up,
+    Direction::Down => Direction::Left,
+    Direction::Left => Direction::Down,
+    Direction::None => Direction::None,
+  }
+}
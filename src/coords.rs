@@ -1,21 +1,44 @@
 use cgmath::Vector2;
+use snake::board::{Board, Cell};
 
-pub fn snake_coordinates(cell_size: Vector2<f32>, position: Vector2<f32>) -> (i32, i32) {
-  let x_100 = (position.x * 100.0).round();
-  let sx_100 = (cell_size.x * 100.0).round();
-
-  let y_100 = (position.y * 100.0).round();
-  let sy_100 = (cell_size.y * 100.0).round();
+/// Size of one cell in screen space, where the board spans -1 to 1 on both axes.
+pub fn cell_size(board: &Board) -> Vector2<f32> {
+  Vector2 {
+    x: 2.0 / board.width as f32,
+    y: 2.0 / board.height as f32,
+  }
+}
 
-  let x = (x_100 / sx_100) as i32;
-  let y = (y_100 / sy_100) as i32;
+/// Screen-space center of `cell`.
+pub fn screen_coordinates(board: &Board, cell: Cell) -> Vector2<f32> {
+  let size = cell_size(board);
 
-  (x, y)
+  Vector2 {
+    x: -1.0 + (cell.0 as f32 + 0.5) * size.x,
+    y: -1.0 + (cell.1 as f32 + 0.5) * size.y,
+  }
 }
 
-pub fn screen_coordinates(cell_size: Vector2<f32>, snake_coords: (i32, i32)) -> Vector2<f32> {
-  Vector2 {
-    x: snake_coords.0 as f32 * cell_size.x,
-    y: snake_coords.1 as f32 * cell_size.y,
+/// Screen-space position between the centers of `from` and `to`. Cells that
+/// aren't neighbours are not blended, the result snaps to `to`.
+pub fn interpolate(board: &Board, from: Cell, to: Cell, alpha: f32) -> Vector2<f32> {
+  let start = screen_coordinates(board, from);
+  let end = screen_coordinates(board, to);
+
+  if (from.0 - to.0).abs() + (from.1 - to.1).abs() != 1 {
+    return end;
   }
+
+  start + (end - start) * alpha
+}
+
+/// Pixel position of the center of `cell` in a window of `window_size`,
+/// measured from the top-left corner the way text is placed.
+pub fn pixel_coordinates(board: &Board, cell: Cell, window_size: (f32, f32)) -> (f32, f32) {
+  let center = screen_coordinates(board, cell);
+
+  (
+    (center.x + 1.0) / 2.0 * window_size.0,
+    (1.0 - center.y) / 2.0 * window_size.1,
+  )
 }
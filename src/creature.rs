@@ -0,0 +1,51 @@
+use crate::board::Cell;
+use crate::util::Direction;
+
+/// Something besides the snakes that moves around the board.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CreatureKind {
+  /// A block going back and forth along a line, turning round whenever its
+  /// way is blocked. Running into one is a crash.
+  Patrol,
+  /// Runs from the nearest snake and is worth points to catch.
+  Mouse,
+  /// Hunts the nearest snake's head. Running into one, or being caught by
+  /// one, is a crash.
+  Chaser,
+}
+
+impl CreatureKind {
+  pub fn name(&self) -> &'static str {
+    match self {
+      CreatureKind::Patrol => "patrol",
+      CreatureKind::Mouse => "mouse",
+      CreatureKind::Chaser => "chaser",
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Creature {
+  pub kind: CreatureKind,
+  pub cell: Cell,
+  /// Where it was before the last tick, for interpolating between ticks.
+  pub previous: Cell,
+  /// The way a patrol is going. Other kinds pick a way each move.
+  pub heading: Direction,
+}
+
+impl Creature {
+  pub fn new(kind: CreatureKind, cell: Cell, heading: Direction) -> Self {
+    Self {
+      kind,
+      cell,
+      previous: cell,
+      heading,
+    }
+  }
+
+  /// Whether a snake's head running into it is a crash rather than a catch.
+  pub fn is_deadly(&self) -> bool {
+    self.kind != CreatureKind::Mouse
+  }
+}
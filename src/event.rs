@@ -0,0 +1,21 @@
+use crate::creature::CreatureKind;
+use crate::power_up::PowerUpKind;
+
+#[derive(Debug, Copy, Clone)]
+pub enum Event {
+  ButtonPressed,
+  FocusChanged,
+  SnakeCrashed,
+  Score(u32),
+  BoardFull,
+  /// Every pellet in a game with a limited number of them was eaten.
+  BoardCleared,
+  /// A snake picked up a power-up.
+  PowerUpCollected(PowerUpKind),
+  /// A power-up a snake had wore off or, for a shield, was used up.
+  PowerUpExpired(PowerUpKind),
+  /// A snake caught a mouse.
+  MouseCaught,
+  /// A snake ran into a creature or was caught by one.
+  CreatureHit(CreatureKind),
+}
@@ -0,0 +1,100 @@
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Why a rule set, level, replay, save, high score table or set of controls
+/// couldn't be loaded.
+#[derive(Debug)]
+pub enum FileError {
+  Io(io::Error),
+  /// The text isn't TOML, or isn't shaped like the file it should be.
+  Toml(toml::de::Error),
+  /// A level's grid can't be read, at a line and column counted from 1.
+  Parse {
+    line: usize,
+    column: usize,
+    message: String,
+  },
+  /// The file reads fine but what it holds can't be used.
+  Invalid(String),
+}
+
+impl fmt::Display for FileError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      FileError::Io(error) => write!(f, "{}", error),
+      FileError::Toml(error) => write!(f, "{}", error),
+      FileError::Parse {
+        line,
+        column,
+        message,
+      } => write!(f, "line {}, column {}: {}", line, column, message),
+      FileError::Invalid(message) => write!(f, "{}", message),
+    }
+  }
+}
+
+impl From<io::Error> for FileError {
+  fn from(error: io::Error) -> Self {
+    FileError::Io(error)
+  }
+}
+
+impl From<toml::de::Error> for FileError {
+  fn from(error: toml::de::Error) -> Self {
+    FileError::Toml(error)
+  }
+}
+
+/// Where `name` lives in the game's folder of `dir`, the user's config or
+/// data directory.
+pub fn user_path(dir: Option<PathBuf>, name: &str) -> Option<PathBuf> {
+  dir.map(|dir| dir.join("snake").join(name))
+}
+
+/// Reads the file at `path` and hands its text to `parse`, or gives `None` if
+/// there's no file there.
+pub fn load<T>(
+  path: &Path,
+  parse: impl FnOnce(&str) -> Result<T, FileError>,
+) -> Result<Option<T>, FileError> {
+  match fs::read_to_string(path) {
+    Ok(text) => parse(&text).map(Some),
+    Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+    Err(error) => Err(error.into()),
+  }
+}
+
+/// Writes `value` to `path` as TOML, making its directory first if need be.
+pub fn save<T: Serialize>(value: &T, path: &Path) -> io::Result<()> {
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir)?;
+  }
+
+  let text = toml::to_string(value).map_err(io::Error::other)?;
+  fs::write(path, text)
+}
+
+/// Loads every file in `dir` ending in `.<extension>`, sorted by file name.
+/// Files that fail to load are reported alongside their path instead.
+pub fn load_dir<T>(
+  dir: &Path,
+  extension: &str,
+  load: impl Fn(&Path) -> Result<T, FileError>,
+) -> Vec<Result<T, (String, FileError)>> {
+  let mut paths: Vec<_> = match fs::read_dir(dir) {
+    Ok(entries) => entries
+      .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+      .filter(|path| path.extension().is_some_and(|other| other == extension))
+      .collect(),
+    Err(_) => Vec::new(),
+  };
+  paths.sort();
+
+  paths
+    .iter()
+    .map(|path| load(path).map_err(|error| (path.display().to_string(), error)))
+    .collect()
+}
@@ -0,0 +1,176 @@
+use crate::file::{self, FileError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Entries kept per table.
+pub const MAX_ENTRIES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighScore {
+  pub name: String,
+  pub score: u32,
+  pub length: u32,
+  /// Game time survived, in seconds.
+  pub seconds: f32,
+  /// When the score was set, as `YYYY-MM-DD`.
+  pub date: String,
+}
+
+/// The best scores for each combination of rule set and level, best first.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighScores {
+  tables: BTreeMap<String, Vec<HighScore>>,
+}
+
+impl HighScores {
+  /// Where the table lives in the user's data directory.
+  pub fn default_path() -> Option<PathBuf> {
+    file::user_path(dirs::data_dir(), "highscores.toml")
+  }
+
+  /// The table name for games played under `rules_name` on `level_name`.
+  pub fn key(rules_name: &str, level_name: &str) -> String {
+    format!("{} / {}", rules_name, level_name)
+  }
+
+  /// Loads the tables from `path`. A missing file is an empty table.
+  pub fn load(path: &Path) -> Result<HighScores, FileError> {
+    let high_scores = file::load(path, |text| Ok(toml::from_str(text)?))?;
+    Ok(high_scores.unwrap_or_default())
+  }
+
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    file::save(self, path)
+  }
+
+  pub fn entries(&self, key: &str) -> &[HighScore] {
+    self
+      .tables
+      .get(key)
+      .map_or(&[], |entries| entries.as_slice())
+  }
+
+  /// Whether `score` would make it onto the table.
+  pub fn qualifies(&self, key: &str, score: u32) -> bool {
+    let entries = self.entries(key);
+    score > 0 && (entries.len() < MAX_ENTRIES || entries.iter().any(|entry| score > entry.score))
+  }
+
+  /// Adds `entry` to the table and returns its rank from 0, or `None` if it
+  /// didn't make the cut.
+  pub fn insert(&mut self, key: &str, entry: HighScore) -> Option<usize> {
+    let entries = self.tables.entry(String::from(key)).or_default();
+    let rank = entries
+      .iter()
+      .position(|existing| entry.score > existing.score)
+      .unwrap_or(entries.len());
+
+    if rank >= MAX_ENTRIES {
+      return None;
+    }
+
+    entries.insert(rank, entry);
+    entries.truncate(MAX_ENTRIES);
+    Some(rank)
+  }
+}
+
+/// Today's date as `YYYY-MM-DD`, in UTC.
+pub fn today() -> String {
+  let seconds = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |duration| duration.as_secs());
+  let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+
+  format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Converts days since 1970-01-01 to a `(year, month, day)` date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+  let z = days + 719_468;
+  let era = z.div_euclid(146_097);
+  let day_of_era = z.rem_euclid(146_097);
+  let year_of_era =
+    (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month_index = (5 * day_of_year + 2) / 153;
+  let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+  let month = if month_index < 10 {
+    month_index + 3
+  } else {
+    month_index - 9
+  } as u32;
+  let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+  (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(score: u32) -> HighScore {
+    HighScore {
+      name: format!("{}", score),
+      score,
+      length: 3,
+      seconds: 1.0,
+      date: String::from("2024-01-01"),
+    }
+  }
+
+  fn scores(high_scores: &HighScores, key: &str) -> Vec<u32> {
+    high_scores
+      .entries(key)
+      .iter()
+      .map(|entry| entry.score)
+      .collect()
+  }
+
+  #[test]
+  fn entries_are_kept_best_first_with_ties_in_the_order_they_were_set() {
+    let mut high_scores = HighScores::default();
+    assert_eq!(high_scores.insert("a", entry(5)), Some(0));
+    assert_eq!(high_scores.insert("a", entry(9)), Some(0));
+    assert_eq!(high_scores.insert("a", entry(5)), Some(2));
+    assert_eq!(high_scores.insert("a", entry(7)), Some(1));
+    assert_eq!(scores(&high_scores, "a"), [9, 7, 5, 5]);
+    assert!(high_scores.entries("b").is_empty());
+  }
+
+  #[test]
+  fn a_full_table_only_takes_better_scores() {
+    let mut high_scores = HighScores::default();
+    for score in 1..=MAX_ENTRIES as u32 {
+      high_scores.insert("a", entry(score * 10));
+    }
+
+    assert!(!high_scores.qualifies("a", 10));
+    assert_eq!(high_scores.insert("a", entry(10)), None);
+    assert!(high_scores.qualifies("a", 11));
+    assert_eq!(high_scores.insert("a", entry(11)), Some(MAX_ENTRIES - 1));
+
+    let kept = scores(&high_scores, "a");
+    assert_eq!(kept.len(), MAX_ENTRIES);
+    assert_eq!(kept.last(), Some(&11));
+  }
+
+  #[test]
+  fn a_score_of_nothing_never_qualifies() {
+    let high_scores = HighScores::default();
+    assert!(!high_scores.qualifies("a", 0));
+    assert!(high_scores.qualifies("a", 1));
+  }
+
+  #[test]
+  fn days_are_turned_into_dates() {
+    assert_eq!(civil_from_days(0), (1970, 1, 1));
+    assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    assert_eq!(civil_from_days(59), (1970, 3, 1));
+    assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+    assert_eq!(civil_from_days(19_723), (2024, 1, 1));
+  }
+}
@@ -1,4 +1,7 @@
 use dynamo_lib::keyboard::*;
+use snake::bindings::{Action, Bindings, SLOTS};
+use snake::turn_queue::TurnQueue;
+use snake::util::Direction;
 
 #[derive(Debug, Default)]
 pub struct Input {
@@ -8,64 +11,187 @@ pub struct Input {
   pub right_pressed: bool,
   pub enter_pressed: bool,
   pub esc_pressed: bool,
+  pub pause_pressed: bool,
+  /// Backspace, for deleting typed text. Not rebindable.
+  pub back_pressed: bool,
+  /// Letters typed since the last frame, for entering text.
+  pub typed: String,
+  /// Name of the key pressed this frame, for rebinding.
+  pub pressed_key: Option<&'static str>,
+  pub bindings: Bindings,
+  /// Directions pressed during play, taken one per tick.
+  turns: TurnQueue,
+  /// The same split by player for versus games: each binding slot's
+  /// direction keys steer one player.
+  player_turns: [TurnQueue; SLOTS],
 }
 
 impl Input {
-  pub fn new() -> Self {
-    Default::default()
+  pub fn new(bindings: Bindings) -> Self {
+    Self {
+      bindings,
+      ..Default::default()
+    }
   }
 
   pub fn update(&mut self, input: KeyboardInput) {
     let pressed = input.state == KeyboardKeyState::Pressed;
-    match input.key {
-      KeyboardKey::Up => {
-        self.up_pressed = pressed;
-      }
-      KeyboardKey::Down => {
-        self.down_pressed = pressed;
-      }
-      KeyboardKey::W => {
-        self.up_pressed = pressed;
-      }
-      KeyboardKey::S => {
-        self.down_pressed = pressed;
-      }
-      KeyboardKey::Left => {
-        self.left_pressed = pressed;
-      }
-      KeyboardKey::Right => {
-        self.right_pressed = pressed;
-      }
-      KeyboardKey::A => {
-        self.left_pressed = pressed;
-      }
-      KeyboardKey::D => {
-        self.right_pressed = pressed;
+    let name = key_name(input.key);
+    if pressed {
+      self.pressed_key = name;
+      if let Some(character) = name.and_then(key_char) {
+        self.typed.push(character);
       }
-      KeyboardKey::Return => {
-        self.enter_pressed = pressed;
+    }
+
+    if let KeyboardKey::Back = input.key {
+      self.back_pressed = pressed;
+    }
+
+    let actions = name.map_or_else(Vec::new, |name| self.bindings.actions(name));
+    for (action, slot) in actions {
+      match action {
+        Action::Up => {
+          self.up_pressed = pressed;
+          self.queue_turn(Direction::Up, pressed, slot);
+        }
+        Action::Down => {
+          self.down_pressed = pressed;
+          self.queue_turn(Direction::Down, pressed, slot);
+        }
+        Action::Left => {
+          self.left_pressed = pressed;
+          self.queue_turn(Direction::Left, pressed, slot);
+        }
+        Action::Right => {
+          self.right_pressed = pressed;
+          self.queue_turn(Direction::Right, pressed, slot);
+        }
+        Action::Confirm => {
+          self.enter_pressed = pressed;
+        }
+        Action::Back => {
+          self.esc_pressed |= pressed;
+        }
+        Action::Pause => {
+          self.pause_pressed |= pressed;
+        }
       }
-      KeyboardKey::Escape => {
-        self.esc_pressed = true;
+    }
+  }
+
+  fn queue_turn(&mut self, direction: Direction, pressed: bool, slot: usize) {
+    if pressed {
+      self.turns.push(direction);
+      if let Some(turns) = self.player_turns.get_mut(slot) {
+        turns.push(direction);
       }
-      _ => (),
     }
   }
 
+  pub fn next_turn(&mut self, travelled: Direction) -> Option<Direction> {
+    self.turns.next_turn(travelled)
+  }
+
+  /// The next turn for `player` in a versus game.
+  pub fn next_player_turn(&mut self, player: usize, travelled: Direction) -> Option<Direction> {
+    self.player_turns.get_mut(player)?.next_turn(travelled)
+  }
+
   pub fn ui_up_pressed(&self) -> bool {
     self.up_pressed
   }
 
   pub fn ui_down_pressed(&self) -> bool {
-    self.down_pressed || self.down_pressed
+    self.down_pressed
   }
 
   pub fn clear(&mut self) {
     self.up_pressed = false;
     self.down_pressed = false;
-    self.up_pressed = false;
-    self.down_pressed = false;
+    self.left_pressed = false;
+    self.right_pressed = false;
     self.enter_pressed = false;
     self.esc_pressed = false;
+    self.pause_pressed = false;
+    self.back_pressed = false;
+    self.typed.clear();
+    self.pressed_key = None;
+    self.turns.clear();
+    for turns in self.player_turns.iter_mut() {
+      turns.clear();
+    }
+  }
+
+  /// Forgets key presses that only count for the frame they happened in.
+  pub fn end_frame(&mut self) {
+    self.back_pressed = false;
+    self.typed.clear();
+    self.pressed_key = None;
+  }
+}
+
+/// The name `key` goes by in the controls file, if it can be bound.
+fn key_name(key: KeyboardKey) -> Option<&'static str> {
+  let name = match key {
+    KeyboardKey::A => "A",
+    KeyboardKey::B => "B",
+    KeyboardKey::C => "C",
+    KeyboardKey::D => "D",
+    KeyboardKey::E => "E",
+    KeyboardKey::F => "F",
+    KeyboardKey::G => "G",
+    KeyboardKey::H => "H",
+    KeyboardKey::I => "I",
+    KeyboardKey::J => "J",
+    KeyboardKey::K => "K",
+    KeyboardKey::L => "L",
+    KeyboardKey::M => "M",
+    KeyboardKey::N => "N",
+    KeyboardKey::O => "O",
+    KeyboardKey::P => "P",
+    KeyboardKey::Q => "Q",
+    KeyboardKey::R => "R",
+    KeyboardKey::S => "S",
+    KeyboardKey::T => "T",
+    KeyboardKey::U => "U",
+    KeyboardKey::V => "V",
+    KeyboardKey::W => "W",
+    KeyboardKey::X => "X",
+    KeyboardKey::Y => "Y",
+    KeyboardKey::Z => "Z",
+    KeyboardKey::Key0 => "0",
+    KeyboardKey::Key1 => "1",
+    KeyboardKey::Key2 => "2",
+    KeyboardKey::Key3 => "3",
+    KeyboardKey::Key4 => "4",
+    KeyboardKey::Key5 => "5",
+    KeyboardKey::Key6 => "6",
+    KeyboardKey::Key7 => "7",
+    KeyboardKey::Key8 => "8",
+    KeyboardKey::Key9 => "9",
+    KeyboardKey::Up => "Up",
+    KeyboardKey::Down => "Down",
+    KeyboardKey::Left => "Left",
+    KeyboardKey::Right => "Right",
+    KeyboardKey::Return => "Return",
+    KeyboardKey::Escape => "Escape",
+    KeyboardKey::Space => "Space",
+    KeyboardKey::Tab => "Tab",
+    KeyboardKey::Back => "Back",
+    _ => return None,
+  };
+
+  Some(name)
+}
+
+/// The character a key types when entering a name.
+fn key_char(name: &str) -> Option<char> {
+  match name {
+    "Space" => Some(' '),
+    _ => name
+      .chars()
+      .next()
+      .filter(|c| name.len() == 1 && c.is_ascii_alphabetic()),
   }
 }
@@ -0,0 +1,391 @@
+use crate::board::{Board, Cell};
+use crate::file::{self, FileError};
+use crate::rules::{Borders, Rules, MAX_BOARD_SIZE};
+use crate::util::Direction;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// A board layout: where the walls are, where the snake starts, which cells
+/// are joined by portals and where patrols start.
+///
+/// Levels are written as an ASCII grid, one character per cell with the top
+/// row first:
+///
+/// ```text
+/// ; comments start with a semicolon
+/// name: Two Rooms
+/// ##########
+/// #...#....#
+/// #.1.#..1.#
+/// #S.......#
+/// ##########
+/// ```
+///
+/// `#` is a wall, `S` the spawn point, `.` or a space an empty cell, and each
+/// digit `1` to `9` marks the two ends of a portal. `<`, `>`, `^` and `v` are
+/// patrols, setting off the way they point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+  pub name: String,
+  pub board: Board,
+  pub walls: Vec<Cell>,
+  pub spawn: Cell,
+  pub portals: Vec<(Cell, Cell)>,
+  /// Where each patrol starts and the way it sets off.
+  pub patrols: Vec<(Cell, Direction)>,
+}
+
+impl Level {
+  /// The plain rectangular board described by `rules`.
+  pub fn from_rules(rules: &Rules) -> Level {
+    let board = Board::new(rules.board_width, rules.board_height);
+    let walls = match rules.borders {
+      Borders::Deadly => board.border(),
+      Borders::Wrap => Vec::new(),
+    };
+
+    Level {
+      name: String::from("Open"),
+      board,
+      walls,
+      spawn: board.center(),
+      portals: Vec::new(),
+      patrols: Vec::new(),
+    }
+  }
+
+  /// Where each of `count` snakes starts. One snake starts on the spawn
+  /// point; more are spread evenly across its row, each moved to the nearest
+  /// open cell if a wall or portal is in the way.
+  pub fn spawns(&self, count: usize) -> Vec<Cell> {
+    if count <= 1 {
+      return vec![self.spawn];
+    }
+
+    let mut spawns: Vec<Cell> = Vec::new();
+    for i in 0..count {
+      let target = (
+        self.board.width * (i as i32 + 1) / (count as i32 + 1),
+        self.spawn.1,
+      );
+      let open = (0..self.board.height)
+        .flat_map(|y| (0..self.board.width).map(move |x| (x, y)))
+        .filter(|cell| self.character(*cell) == '.' || *cell == self.spawn)
+        .filter(|cell| !spawns.contains(cell))
+        .min_by_key(|cell| (cell.0 - target.0).abs() + (cell.1 - target.1).abs());
+
+      if let Some(cell) = open {
+        spawns.push(cell);
+      }
+    }
+
+    spawns
+  }
+
+  /// The levels in the `levels` directory. Files that fail to load are
+  /// reported and skipped.
+  pub fn available() -> Vec<Level> {
+    let mut levels = Vec::new();
+
+    for result in Level::load_dir(Path::new("levels")) {
+      match result {
+        Ok(level) => levels.push(level),
+        Err((path, error)) => eprintln!("Couldn't load level {}: {}", path, error),
+      }
+    }
+
+    levels
+  }
+
+  pub fn load(path: &Path) -> Result<Level, FileError> {
+    let text = fs::read_to_string(path)?;
+    let name = path
+      .file_stem()
+      .map(|stem| stem.to_string_lossy().into_owned())
+      .unwrap_or_default();
+
+    Level::parse(&name, &text)
+  }
+
+  /// Every `.txt` file in `dir`, sorted by file name. Files that fail to
+  /// load are reported alongside their path instead.
+  pub fn load_dir(dir: &Path) -> Vec<Result<Level, (String, FileError)>> {
+    file::load_dir(dir, "txt", Level::load)
+  }
+
+  /// Parses a level, using `name` unless the text has a `name:` line.
+  pub fn parse(name: &str, text: &str) -> Result<Level, FileError> {
+    let mut name = String::from(name);
+    let mut rows: Vec<(usize, &str)> = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+      let line_number = i + 1;
+      if line.starts_with(';') {
+        continue;
+      }
+      if let Some(header) = line.strip_prefix("name:") {
+        if !rows.is_empty() {
+          return Err(parse_error(
+            line_number,
+            1,
+            "name must come before the grid",
+          ));
+        }
+        name = String::from(header.trim());
+        continue;
+      }
+      if rows.is_empty() && line.trim().is_empty() {
+        continue;
+      }
+      rows.push((line_number, line.trim_end()));
+    }
+
+    while rows.last().is_some_and(|(_, row)| row.is_empty()) {
+      rows.pop();
+    }
+    if rows.is_empty() {
+      return Err(parse_error(1, 1, "level has no grid"));
+    }
+
+    let too_big = format!(
+      "the level can't be bigger than {} by {}",
+      MAX_BOARD_SIZE, MAX_BOARD_SIZE
+    );
+    if let Some((line, _)) = rows.get(MAX_BOARD_SIZE as usize) {
+      return Err(parse_error(*line, 1, &too_big));
+    }
+    if let Some((line, _)) = rows
+      .iter()
+      .find(|(_, row)| row.chars().count() > MAX_BOARD_SIZE as usize)
+    {
+      return Err(parse_error(*line, MAX_BOARD_SIZE as usize + 1, &too_big));
+    }
+
+    let width = rows
+      .iter()
+      .map(|(_, row)| row.chars().count())
+      .max()
+      .unwrap_or(0);
+    let board = Board::new(width as i32, rows.len() as i32);
+
+    let mut walls = Vec::new();
+    let mut patrols = Vec::new();
+    let mut spawn = None;
+    let mut portal_ends: Vec<Vec<(Cell, usize, usize)>> = vec![Vec::new(); 9];
+
+    for (row_index, (line, row)) in rows.iter().enumerate() {
+      let y = board.height - 1 - row_index as i32;
+      for (x, character) in row.chars().enumerate() {
+        let column = x + 1;
+        let cell = (x as i32, y);
+        match character {
+          '.' | ' ' => {}
+          '#' => walls.push(cell),
+          '^' => patrols.push((cell, Direction::Up)),
+          'v' => patrols.push((cell, Direction::Down)),
+          '<' => patrols.push((cell, Direction::Left)),
+          '>' => patrols.push((cell, Direction::Right)),
+          'S' => {
+            if spawn.is_some() {
+              return Err(parse_error(*line, column, "more than one spawn point"));
+            }
+            spawn = Some(cell);
+          }
+          '1'..='9' => {
+            let ends = &mut portal_ends[character as usize - '1' as usize];
+            if ends.len() == 2 {
+              let message = format!("portal {} has more than two ends", character);
+              return Err(parse_error(*line, column, &message));
+            }
+            ends.push((cell, *line, column));
+          }
+          _ => {
+            let message = format!("unexpected character '{}'", character);
+            return Err(parse_error(*line, column, &message));
+          }
+        }
+      }
+    }
+
+    let spawn = spawn.ok_or_else(|| parse_error(rows[0].0, 1, "level has no spawn point"))?;
+
+    let mut portals = Vec::new();
+    for (i, ends) in portal_ends.iter().enumerate() {
+      match ends.as_slice() {
+        [] => {}
+        [(a, _, _), (b, _, _)] => portals.push((*a, *b)),
+        [(_, line, column)] => {
+          let message = format!("portal {} has only one end", i + 1);
+          return Err(parse_error(*line, *column, &message));
+        }
+        _ => unreachable!(),
+      }
+    }
+
+    Ok(Level {
+      name,
+      board,
+      walls,
+      spawn,
+      portals,
+      patrols,
+    })
+  }
+
+  fn character(&self, cell: Cell) -> char {
+    if cell == self.spawn {
+      return 'S';
+    }
+    if self.walls.contains(&cell) {
+      return '#';
+    }
+
+    for (i, (a, b)) in self.portals.iter().enumerate() {
+      if *a == cell || *b == cell {
+        return std::char::from_digit(i as u32 + 1, 10).unwrap_or('.');
+      }
+    }
+    for (start, heading) in self.patrols.iter() {
+      if *start == cell {
+        return match heading {
+          Direction::Up => '^',
+          Direction::Down => 'v',
+          Direction::Left => '<',
+          Direction::Right | Direction::None => '>',
+        };
+      }
+    }
+
+    '.'
+  }
+}
+
+/// Writes the level back out in the format `parse` reads.
+impl fmt::Display for Level {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "name: {}", self.name)?;
+
+    for y in (0..self.board.height).rev() {
+      let row: String = (0..self.board.width)
+        .map(|x| self.character((x, y)))
+        .collect();
+      writeln!(f, "{}", row)?;
+    }
+
+    Ok(())
+  }
+}
+
+impl Serialize for Level {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&self.to_string())
+  }
+}
+
+impl<'de> Deserialize<'de> for Level {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Level, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    Level::parse("", &text).map_err(D::Error::custom)
+  }
+}
+
+fn parse_error(line: usize, column: usize, message: &str) -> FileError {
+  FileError::Parse {
+    line,
+    column,
+    message: String::from(message),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn error_at(text: &str) -> (usize, usize, String) {
+    match Level::parse("test", text) {
+      Err(FileError::Parse {
+        line,
+        column,
+        message,
+      }) => (line, column, message),
+      result => panic!("expected a parse error, got {:?}", result),
+    }
+  }
+
+  #[test]
+  fn an_unexpected_character_is_reported_where_it_is() {
+    let (line, column, message) = error_at("; a comment\n#####\n#.S.#\n#..x#\n#####\n");
+    assert_eq!((line, column), (4, 4));
+    assert!(message.contains("'x'"));
+  }
+
+  #[test]
+  fn a_level_needs_a_spawn_point() {
+    let (line, column, message) = error_at("\n#####\n#...#\n#####\n");
+    assert_eq!((line, column), (2, 1));
+    assert!(message.contains("no spawn"));
+  }
+
+  #[test]
+  fn a_level_can_only_have_one_spawn_point() {
+    let (line, column, message) = error_at("#####\n#.S.#\n#S..#\n#####\n");
+    assert_eq!((line, column), (3, 2));
+    assert!(message.contains("more than one spawn"));
+  }
+
+  #[test]
+  fn a_portal_needs_exactly_two_ends() {
+    let (line, column, message) = error_at("#####\n#.S.#\n#..1#\n#####\n");
+    assert_eq!((line, column), (3, 4));
+    assert!(message.contains("only one end"));
+
+    let (line, column, message) = error_at("#####\n#2S2#\n#..2#\n#####\n");
+    assert_eq!((line, column), (3, 4));
+    assert!(message.contains("more than two ends"));
+  }
+
+  #[test]
+  fn a_level_can_be_no_bigger_than_a_rule_set_board() {
+    let size = MAX_BOARD_SIZE as usize;
+    let mut text = format!("S{}\n", ".".repeat(size - 1));
+    text.push_str(&format!("{}\n", ".".repeat(size)).repeat(size - 1));
+    assert!(Level::parse("test", &text).is_ok());
+
+    let (line, column, message) = error_at(&format!("{}.\n", text));
+    assert_eq!((line, column), (size + 1, 1));
+    assert!(message.contains("bigger than"));
+
+    let wide = format!("S{}\n", ".".repeat(size));
+    let (line, column, message) = error_at(&wide);
+    assert_eq!((line, column), (1, size + 1));
+    assert!(message.contains("bigger than"));
+  }
+
+  #[test]
+  fn a_written_level_reads_back_the_same() {
+    let text = "name: Everything\n#######\n#1...2#\n#.>.v.#\n#..S..#\n#^...<#\n#2...1#\n#######\n";
+    let level = Level::parse("", text).unwrap();
+    assert_eq!(level.name, "Everything");
+    assert_eq!(level.portals.len(), 2);
+    assert_eq!(level.patrols.len(), 4);
+    assert_eq!(level.to_string(), text);
+
+    let parsed = Level::parse("", &level.to_string()).unwrap();
+    assert_eq!(parsed, level);
+  }
+
+  #[test]
+  fn the_bundled_levels_load() {
+    let levels = Level::load_dir(Path::new("levels"));
+    assert!(!levels.is_empty());
+
+    for result in levels {
+      if let Err((path, error)) = result {
+        panic!("{}: {}", path, error);
+      }
+    }
+  }
+}
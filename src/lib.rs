@@ -0,0 +1,24 @@
+pub mod bindings;
+pub mod board;
+pub mod controller;
+pub mod creature;
+pub mod event;
+pub mod file;
+pub mod highscores;
+pub mod level;
+pub mod menu;
+pub mod net;
+pub mod occupancy;
+pub mod options;
+pub mod pellet;
+pub mod power_up;
+pub mod replay;
+pub mod rules;
+pub mod save;
+pub mod server;
+pub mod session;
+pub mod simulation;
+pub mod snake;
+pub mod ticker;
+pub mod turn_queue;
+pub mod util;
@@ -1,17 +1,98 @@
 use dynamo_lib::start;
+use snake::controller::{self, BOT_NAMES};
+use snake::net::{Client, DEFAULT_PORT};
+use snake::options::{Options, USAGE};
+use snake::replay::Replay;
 
 mod coords;
 mod input;
-mod pellet;
-mod snake;
 mod snake_game;
 mod state;
 mod system;
-mod util;
 
 use snake_game::SnakeGame;
 
 fn main() {
-  let snake_game = SnakeGame::new();
+  let options = match Options::from_args(std::env::args().skip(1)) {
+    Ok(options) => options,
+    Err(error) => {
+      eprintln!("{}\n{}", error, USAGE);
+      std::process::exit(2);
+    }
+  };
+
+  let playback = options
+    .replay
+    .as_ref()
+    .map(|path| match Replay::load(path) {
+      Ok(replay) => replay,
+      Err(error) => {
+        eprintln!("{}: {}", path.display(), error);
+        std::process::exit(1);
+      }
+    });
+
+  if let (true, Some(replay)) = (options.verify, &playback) {
+    let simulation = replay.run();
+    println!(
+      "score: {} ticks: {}",
+      simulation.snakes[0].score, simulation.tick
+    );
+    return;
+  }
+
+  if let Some(games) = options.benchmark {
+    run_benchmark(&options, games);
+    return;
+  }
+
+  let client = options
+    .connect
+    .as_ref()
+    .map(|address| match connect(address) {
+      Ok(client) => client,
+      Err(error) => {
+        eprintln!("Couldn't connect to {}: {}", address, error);
+        std::process::exit(1);
+      }
+    });
+
+  let snake_game = SnakeGame::new(options, playback, client);
   start("Snake", Box::new(snake_game));
 }
+
+/// Connects to a game server, on the default port unless `address` names one.
+fn connect(address: &str) -> std::io::Result<Client> {
+  if address.contains(':') {
+    Client::connect(address)
+  } else {
+    Client::connect((address, DEFAULT_PORT))
+  }
+}
+
+fn run_benchmark(options: &Options, games: u32) {
+  let names = match &options.bot {
+    Some(name) => vec![name.as_str()],
+    None => BOT_NAMES.to_vec(),
+  };
+  let rules = options.rules.clone().unwrap_or_default();
+  let seed = options.seed.unwrap_or(0);
+
+  println!(
+    "{:<16} {:>6} {:>10} {:>10} {:>6}",
+    "bot", "games", "avg score", "avg ticks", "wins"
+  );
+  for name in names {
+    if let Some(mut bot) = controller::bot(name) {
+      let result = controller::benchmark(bot.as_mut(), &rules, games, seed);
+      println!(
+        "{:<16} {:>6} {:>10.1} {:>10.1} {:>6}",
+        bot.name(),
+        result.games,
+        result.average_score(),
+        result.average_ticks(),
+        result.wins
+      );
+    }
+  }
+}
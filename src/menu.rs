@@ -0,0 +1,251 @@
+/// One entry in a `Menu`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuItem<A> {
+  pub label: String,
+  pub action: A,
+  /// Disabled items are shown but can't be focused or chosen.
+  pub enabled: bool,
+}
+
+/// A column of items to move between and choose from, with the keyboard or a
+/// pointer. Choosing an item gives back its action for the screen to act on.
+///
+/// The menu only knows its rows: the first starts at `origin` and each one is
+/// `row_height` below the last, in whatever units the frontend draws in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Menu<A> {
+  items: Vec<MenuItem<A>>,
+  focus: usize,
+  pub origin: (f32, f32),
+  pub row_height: f32,
+  /// How far right of `origin` the pointer still counts as over an item.
+  pub width: f32,
+}
+
+impl<A: Copy + PartialEq> Menu<A> {
+  pub fn new(origin: (f32, f32), row_height: f32, width: f32) -> Self {
+    Self {
+      items: Vec::new(),
+      focus: 0,
+      origin,
+      row_height,
+      width,
+    }
+  }
+
+  /// Adds an item to the bottom of the menu.
+  pub fn item(mut self, label: &str, action: A) -> Self {
+    self.items.push(MenuItem {
+      label: String::from(label),
+      action,
+      enabled: true,
+    });
+    self
+  }
+
+  pub fn items(&self) -> &[MenuItem<A>] {
+    &self.items
+  }
+
+  /// The index of the focused item, `None` if every item is disabled.
+  pub fn focus_index(&self) -> Option<usize> {
+    match self.items.get(self.focus) {
+      Some(item) if item.enabled => Some(self.focus),
+      _ => None,
+    }
+  }
+
+  /// The focused item's action.
+  pub fn focused(&self) -> Option<A> {
+    self.focus_index().map(|index| self.items[index].action)
+  }
+
+  pub fn set_label(&mut self, action: A, label: String) {
+    if let Some(item) = self.items.iter_mut().find(|item| item.action == action) {
+      item.label = label;
+    }
+  }
+
+  /// Enables or disables the item for `action`, moving the focus on if it
+  /// was on an item that has just been disabled.
+  pub fn set_enabled(&mut self, action: A, enabled: bool) {
+    if let Some(item) = self.items.iter_mut().find(|item| item.action == action) {
+      item.enabled = enabled;
+    }
+    if self.focus_index().is_none() {
+      self.focus_next();
+    }
+  }
+
+  /// Focuses the item for `action`. Returns false if there's no such item or
+  /// it's disabled.
+  pub fn focus(&mut self, action: A) -> bool {
+    match self
+      .items
+      .iter()
+      .position(|item| item.action == action && item.enabled)
+    {
+      Some(index) => {
+        self.focus = index;
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Focuses the first item that isn't disabled.
+  pub fn focus_first(&mut self) {
+    self.focus = self.items.len().saturating_sub(1);
+    self.focus_next();
+  }
+
+  /// Moves the focus down to the next enabled item, wrapping around to the
+  /// top. Returns whether the focus moved.
+  pub fn focus_next(&mut self) -> bool {
+    self.step(1)
+  }
+
+  /// Moves the focus up to the previous enabled item, wrapping around to the
+  /// bottom. Returns whether the focus moved.
+  pub fn focus_previous(&mut self) -> bool {
+    self.step(self.items.len().saturating_sub(1))
+  }
+
+  fn step(&mut self, offset: usize) -> bool {
+    let count = self.items.len();
+    let mut index = self.focus;
+    for _ in 0..count {
+      index = (index + offset) % count;
+      if self.items[index].enabled {
+        let moved = index != self.focus;
+        self.focus = index;
+        return moved;
+      }
+    }
+    false
+  }
+
+  /// Where the row for item `index` starts.
+  pub fn position(&self, index: usize) -> (f32, f32) {
+    (
+      self.origin.0,
+      self.origin.1 + self.row_height * index as f32,
+    )
+  }
+
+  /// The item under `point`, if any.
+  pub fn item_at(&self, point: (f32, f32)) -> Option<usize> {
+    let (x, y) = (point.0 - self.origin.0, point.1 - self.origin.1);
+    if x < 0.0 || x >= self.width || y < 0.0 {
+      return None;
+    }
+
+    let index = (y / self.row_height) as usize;
+    if index < self.items.len() {
+      Some(index)
+    } else {
+      None
+    }
+  }
+
+  /// Focuses the enabled item under the pointer. Returns whether the focus
+  /// moved.
+  pub fn hover(&mut self, point: (f32, f32)) -> bool {
+    match self.item_at(point) {
+      Some(index) if self.items[index].enabled && index != self.focus => {
+        self.focus = index;
+        true
+      }
+      _ => false,
+    }
+  }
+
+  /// Focuses and chooses the enabled item under the pointer.
+  pub fn click(&mut self, point: (f32, f32)) -> Option<A> {
+    self.hover(point);
+    match self.item_at(point) {
+      Some(index) if index == self.focus && self.items[index].enabled => {
+        Some(self.items[index].action)
+      }
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn menu() -> Menu<char> {
+    Menu::new((10.0, 100.0), 20.0, 50.0)
+      .item("Alpha", 'a')
+      .item("Beta", 'b')
+      .item("Gamma", 'c')
+  }
+
+  #[test]
+  fn stepping_wraps_around_both_ends() {
+    let mut menu = menu();
+    assert_eq!(menu.focused(), Some('a'));
+    assert!(menu.focus_previous());
+    assert_eq!(menu.focused(), Some('c'));
+    assert!(menu.focus_next());
+    assert_eq!(menu.focused(), Some('a'));
+  }
+
+  #[test]
+  fn disabled_items_are_stepped_over() {
+    let mut menu = menu();
+    menu.set_enabled('b', false);
+    assert!(menu.focus_next());
+    assert_eq!(menu.focused(), Some('c'));
+    assert!(menu.focus_previous());
+    assert_eq!(menu.focused(), Some('a'));
+    assert!(!menu.focus('b'));
+    assert_eq!(menu.focused(), Some('a'));
+  }
+
+  #[test]
+  fn disabling_the_focused_item_moves_the_focus_on() {
+    let mut menu = menu();
+    menu.set_enabled('a', false);
+    assert_eq!(menu.focused(), Some('b'));
+
+    menu.set_enabled('b', false);
+    menu.set_enabled('c', false);
+    assert_eq!(menu.focus_index(), None);
+    assert!(!menu.focus_next());
+
+    menu.set_enabled('b', true);
+    assert_eq!(menu.focused(), Some('b'));
+  }
+
+  #[test]
+  fn the_pointer_finds_the_row_it_is_over() {
+    let menu = menu();
+    assert_eq!(menu.item_at((10.0, 100.0)), Some(0));
+    assert_eq!(menu.item_at((59.0, 145.0)), Some(2));
+    assert_eq!(menu.item_at((60.0, 100.0)), None);
+    assert_eq!(menu.item_at((9.0, 100.0)), None);
+    assert_eq!(menu.item_at((10.0, 99.0)), None);
+    assert_eq!(menu.item_at((10.0, 160.0)), None);
+  }
+
+  #[test]
+  fn hovering_and_clicking_skip_disabled_items() {
+    let mut menu = menu();
+    menu.set_enabled('c', false);
+
+    assert!(menu.hover((20.0, 125.0)));
+    assert!(!menu.hover((20.0, 125.0)));
+    assert_eq!(menu.focused(), Some('b'));
+
+    assert!(!menu.hover((20.0, 145.0)));
+    assert_eq!(menu.click((20.0, 145.0)), None);
+    assert_eq!(menu.focused(), Some('b'));
+
+    assert_eq!(menu.click((20.0, 105.0)), Some('a'));
+    assert_eq!(menu.focused(), Some('a'));
+    assert_eq!(menu.click((200.0, 105.0)), None);
+  }
+}
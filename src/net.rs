@@ -0,0 +1,370 @@
+use crate::event::Event;
+use crate::replay::Replay;
+use crate::simulation::Simulation;
+use crate::util::Direction;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+/// What the server sends its clients.
+///
+/// Games run in lockstep: the server picks each player's turn for every tick
+/// and every client steps its own copy of the game with them, so the games
+/// never drift apart as long as the simulation stays deterministic.
+///
+/// On the wire each message is a line of text. A game's setup follows its
+/// line as a replay with no turns, `<bytes>` long.
+///
+/// ```text
+/// game <player> <players> <bytes>
+/// tick <one direction letter per player, - for none>
+/// full
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+  /// A round is starting. The client plays snake `player` of `players`.
+  Game {
+    player: usize,
+    players: usize,
+    setup: Box<Replay>,
+  },
+  /// The turn each player took this tick.
+  Tick(Vec<Direction>),
+  /// The server already has all the players it wants.
+  Full,
+}
+
+/// What a client sends the server, one line each:
+///
+/// ```text
+/// ready
+/// unready
+/// turn <direction letter>
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ClientMessage {
+  /// The player wants to play the next round.
+  Ready,
+  /// The player no longer wants to play the next round.
+  Unready,
+  Turn(Direction),
+}
+
+impl ServerMessage {
+  pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+    match self {
+      ServerMessage::Game {
+        player,
+        players,
+        setup,
+      } => {
+        let setup = setup.to_string();
+        write!(
+          writer,
+          "game {} {} {}\n{}",
+          player,
+          players,
+          setup.len(),
+          setup
+        )?;
+      }
+      ServerMessage::Tick(turns) => {
+        let letters: String = turns.iter().map(Direction::letter).collect();
+        writeln!(writer, "tick {}", letters)?;
+      }
+      ServerMessage::Full => writeln!(writer, "full")?,
+    }
+
+    writer.flush()
+  }
+
+  /// Reads the next message, or `None` once the server has hung up.
+  pub fn read(reader: &mut impl BufRead) -> io::Result<Option<ServerMessage>> {
+    let line = match read_line(reader)? {
+      Some(line) => line,
+      None => return Ok(None),
+    };
+    let words: Vec<&str> = line.split_whitespace().collect();
+
+    let message = match words.as_slice() {
+      ["game", player, players, bytes] => {
+        let mut setup = vec![0; number(bytes)?];
+        reader.read_exact(&mut setup)?;
+        let setup = String::from_utf8(setup).map_err(|_| invalid("game setup isn't text"))?;
+        let setup = Replay::parse(&setup).map_err(|error| invalid(&error.to_string()))?;
+
+        ServerMessage::Game {
+          player: number(player)?,
+          players: number(players)?,
+          setup: Box::new(setup),
+        }
+      }
+      ["tick", letters] => ServerMessage::Tick(directions(letters)?),
+      ["full"] => ServerMessage::Full,
+      _ => return Err(invalid(&format!("unexpected message: {}", line))),
+    };
+
+    Ok(Some(message))
+  }
+}
+
+impl ClientMessage {
+  pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+    match self {
+      ClientMessage::Ready => writeln!(writer, "ready")?,
+      ClientMessage::Unready => writeln!(writer, "unready")?,
+      ClientMessage::Turn(direction) => writeln!(writer, "turn {}", direction.letter())?,
+    }
+
+    writer.flush()
+  }
+
+  /// Reads the next message, or `None` once the client has hung up.
+  pub fn read(reader: &mut impl BufRead) -> io::Result<Option<ClientMessage>> {
+    let line = match read_line(reader)? {
+      Some(line) => line,
+      None => return Ok(None),
+    };
+    let words: Vec<&str> = line.split_whitespace().collect();
+
+    let message = match words.as_slice() {
+      ["ready"] => ClientMessage::Ready,
+      ["unready"] => ClientMessage::Unready,
+      ["turn", letter] => match directions(letter)?.as_slice() {
+        [direction] => ClientMessage::Turn(*direction),
+        _ => return Err(invalid(&format!("unexpected message: {}", line))),
+      },
+      _ => return Err(invalid(&format!("unexpected message: {}", line))),
+    };
+
+    Ok(Some(message))
+  }
+}
+
+/// The client's side of a networked game: a connection to the server and
+/// the local copy of the game it keeps in step.
+pub struct Client {
+  stream: TcpStream,
+  messages: Receiver<io::Result<ServerMessage>>,
+  /// The snake this client steers in the current round, `None` between
+  /// rounds.
+  pub player: Option<usize>,
+}
+
+impl Client {
+  /// Connects to a server, reading what it sends on a background thread.
+  pub fn connect(address: impl ToSocketAddrs) -> io::Result<Client> {
+    let stream = TcpStream::connect(address)?;
+    stream.set_nodelay(true)?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let (sender, messages) = mpsc::channel();
+    thread::spawn(move || loop {
+      let message = match ServerMessage::read(&mut reader) {
+        Ok(Some(message)) => Ok(message),
+        Ok(None) => Err(io::Error::new(
+          io::ErrorKind::UnexpectedEof,
+          "the server closed the connection",
+        )),
+        Err(error) => Err(error),
+      };
+
+      let failed = message.is_err();
+      if sender.send(message).is_err() || failed {
+        break;
+      }
+    });
+
+    Ok(Client {
+      stream,
+      messages,
+      player: None,
+    })
+  }
+
+  pub fn send(&mut self, message: ClientMessage) -> io::Result<()> {
+    message.write(&mut self.stream)
+  }
+
+  /// Applies everything the server has sent since the last call to
+  /// `simulation`, replacing it when a new round starts. Returns whether a
+  /// tick or round arrived, and the events of the ticks that were run.
+  pub fn update(&mut self, simulation: &mut Simulation) -> io::Result<(bool, Vec<Event>)> {
+    let mut changed = false;
+    let mut events = Vec::new();
+
+    loop {
+      let message = match self.messages.try_recv() {
+        Ok(message) => message?,
+        Err(TryRecvError::Empty) => break,
+        Err(TryRecvError::Disconnected) => {
+          return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "the server closed the connection",
+          ))
+        }
+      };
+      changed = true;
+
+      match message {
+        ServerMessage::Game {
+          player,
+          players,
+          setup,
+        } => {
+          *simulation = setup.simulation_for(players);
+          self.player = Some(player);
+        }
+        ServerMessage::Tick(turns) => {
+          let turns: Vec<&[Direction]> = turns.iter().map(std::slice::from_ref).collect();
+          events.extend(simulation.step_players(&turns));
+        }
+        ServerMessage::Full => {
+          return Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            "the server is full",
+          ))
+        }
+      }
+    }
+
+    Ok((changed, events))
+  }
+}
+
+fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+  let mut line = String::new();
+  if reader.read_line(&mut line)? == 0 {
+    return Ok(None);
+  }
+
+  Ok(Some(String::from(line.trim_end())))
+}
+
+fn number(word: &str) -> io::Result<usize> {
+  word
+    .parse()
+    .map_err(|_| invalid(&format!("invalid number: {}", word)))
+}
+
+fn directions(letters: &str) -> io::Result<Vec<Direction>> {
+  letters
+    .chars()
+    .map(|letter| {
+      Direction::from_letter(letter)
+        .ok_or_else(|| invalid(&format!("invalid direction: {}", letter)))
+    })
+    .collect()
+}
+
+fn invalid(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rules::{Rules, SpeedCurve};
+  use crate::server::Server;
+  use std::time::Duration;
+
+  /// Starts a server for two players on a free port and connects two clients
+  /// to it.
+  fn start() -> [Client; 2] {
+    let rules = Rules {
+      speed: SpeedCurve {
+        start: 100.0,
+        increase: 0.0,
+        max: 100.0,
+      },
+      ..Default::default()
+    };
+    let mut server = Server::bind(("127.0.0.1", 0), 2, Some(9), rules).unwrap();
+    let address = server.local_addr();
+    thread::spawn(move || server.run());
+
+    [
+      Client::connect(address).unwrap(),
+      Client::connect(address).unwrap(),
+    ]
+  }
+
+  fn next_message(client: &Client) -> ServerMessage {
+    client
+      .messages
+      .recv_timeout(Duration::from_secs(5))
+      .expect("nothing from the server")
+      .expect("couldn't read the server's message")
+  }
+
+  fn next_tick(client: &Client) -> Vec<Direction> {
+    match next_message(client) {
+      ServerMessage::Tick(turns) => turns,
+      message => panic!("expected a tick, got {:?}", message),
+    }
+  }
+
+  /// Waits for the next round to start and returns the player each client
+  /// steers.
+  fn next_round(clients: &[Client]) -> Vec<usize> {
+    clients
+      .iter()
+      .map(|client| match next_message(client) {
+        ServerMessage::Game {
+          player,
+          players,
+          setup,
+        } => {
+          assert_eq!(players, clients.len());
+          assert_eq!(setup.seed, 9);
+          player
+        }
+        message => panic!("expected a round to start, got {:?}", message),
+      })
+      .collect()
+  }
+
+  #[test]
+  fn both_clients_see_the_same_ticks() {
+    let mut clients = start();
+    for client in clients.iter_mut() {
+      client.send(ClientMessage::Ready).unwrap();
+    }
+
+    let players = next_round(&clients);
+    let mut sorted = players.clone();
+    sorted.sort();
+    assert_eq!(sorted, vec![0, 1]);
+
+    let chosen = [Direction::Up, Direction::Down];
+    for (client, player) in clients.iter_mut().zip(players.iter()) {
+      client.send(ClientMessage::Turn(chosen[*player])).unwrap();
+    }
+
+    // Both turns show up within a few ticks, long before either snake gets
+    // near a wall and the round ends.
+    let turned = |ticks: &[Vec<Direction>]| {
+      chosen
+        .iter()
+        .enumerate()
+        .all(|(player, direction)| ticks.iter().any(|turns| turns[player] == *direction))
+    };
+    let mut first = Vec::new();
+    while !turned(&first) {
+      first.push(next_tick(&clients[0]));
+    }
+    let second: Vec<Vec<Direction>> = first.iter().map(|_| next_tick(&clients[1])).collect();
+    assert_eq!(first, second);
+
+    for (player, direction) in chosen.iter().enumerate() {
+      let turn = first
+        .iter()
+        .map(|turns| turns[player])
+        .find(|turn| *turn != Direction::None);
+      assert_eq!(turn, Some(*direction));
+    }
+  }
+}
@@ -0,0 +1,83 @@
+use crate::board::{Board, Cell};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Tile {
+  Empty,
+  Wall,
+  Snake,
+  Pellet,
+  PowerUp,
+  /// One end of a portal. Snakes pass through it, so nothing stays on it.
+  Portal,
+  Creature,
+}
+
+/// What is in each cell of the board, kept up to date as the game runs so
+/// collisions and pellet spawning don't have to search the snake or walls.
+pub struct Occupancy {
+  board: Board,
+  tiles: Vec<Tile>,
+  free: usize,
+}
+
+impl Occupancy {
+  pub fn new(board: Board) -> Self {
+    let size = (board.width * board.height) as usize;
+
+    Self {
+      board,
+      tiles: vec![Tile::Empty; size],
+      free: size,
+    }
+  }
+
+  /// Anything off the board counts as a wall.
+  pub fn get(&self, cell: Cell) -> Tile {
+    match self.index(cell) {
+      Some(index) => self.tiles[index],
+      None => Tile::Wall,
+    }
+  }
+
+  pub fn set(&mut self, cell: Cell, tile: Tile) {
+    if let Some(index) = self.index(cell) {
+      let previous = self.tiles[index];
+      if previous == Tile::Empty && tile != Tile::Empty {
+        self.free -= 1;
+      } else if previous != Tile::Empty && tile == Tile::Empty {
+        self.free += 1;
+      }
+
+      self.tiles[index] = tile;
+    }
+  }
+
+  /// Number of empty cells.
+  pub fn free(&self) -> usize {
+    self.free
+  }
+
+  /// The `n`th empty cell, counting row by row from the bottom-left corner.
+  pub fn nth_free(&self, n: usize) -> Option<Cell> {
+    self
+      .tiles
+      .iter()
+      .enumerate()
+      .filter(|(_, tile)| **tile == Tile::Empty)
+      .nth(n)
+      .map(|(index, _)| self.cell(index))
+  }
+
+  fn index(&self, cell: Cell) -> Option<usize> {
+    if self.board.contains(cell) {
+      Some((cell.1 * self.board.width + cell.0) as usize)
+    } else {
+      None
+    }
+  }
+
+  fn cell(&self, index: usize) -> Cell {
+    let index = index as i32;
+    (index % self.board.width, index / self.board.width)
+  }
+}
@@ -0,0 +1,91 @@
+use crate::controller;
+use crate::rules::Rules;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub const USAGE: &str =
+  "usage: snake [--seed <number>] [--record <file>] [--replay <file> [--verify]]
+             [--rules <name>] [--bot <name>] [--benchmark <games>] [--connect <address>]
+       snake-server [--port <number>] [--players <number>] [--seed <number>]
+                    [--rules <name>]";
+
+/// Command-line options shared by the game's frontends.
+#[derive(Debug, Default)]
+pub struct Options {
+  /// Seed every game with this instead of a fresh random one.
+  pub seed: Option<u64>,
+  /// Write a replay of each finished game here.
+  pub record: Option<PathBuf>,
+  /// Play back this replay instead of taking input.
+  pub replay: Option<PathBuf>,
+  /// Run the replay without a window and print how it ended.
+  pub verify: bool,
+  /// Bot to use for the menu's demo or for benchmarking.
+  pub bot: Option<String>,
+  /// Play this many games with each bot, or just `bot`, without a window
+  /// and print their average scores.
+  pub benchmark: Option<u32>,
+  /// Rule set to select in the menu, benchmark under or serve, given by name
+  /// or as the path to a rules file.
+  pub rules: Option<Rules>,
+  /// Join the game server at this address instead of playing locally.
+  pub connect: Option<String>,
+  /// Port for the game server to listen on.
+  pub port: Option<u16>,
+  /// Players the game server waits for before starting a round.
+  pub players: Option<usize>,
+}
+
+impl Options {
+  pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options::default();
+
+    while let Some(arg) = args.next() {
+      match arg.as_str() {
+        "--seed" => options.seed = Some(value(&arg, args.next())?),
+        "--record" => options.record = Some(value(&arg, args.next())?),
+        "--replay" => options.replay = Some(value(&arg, args.next())?),
+        "--verify" => options.verify = true,
+        "--bot" => options.bot = Some(value(&arg, args.next())?),
+        "--benchmark" => options.benchmark = Some(value(&arg, args.next())?),
+        "--rules" => {
+          let name: String = value(&arg, args.next())?;
+          let rules = Rules::find(&name)
+            .map_err(|error| format!("couldn't load rules {}: {}", name, error))?;
+          options.rules = Some(rules);
+        }
+        "--connect" => options.connect = Some(value(&arg, args.next())?),
+        "--port" => options.port = Some(value(&arg, args.next())?),
+        "--players" => options.players = Some(value(&arg, args.next())?),
+        _ => return Err(format!("unknown argument: {}", arg)),
+      }
+    }
+
+    if options.verify && options.replay.is_none() {
+      return Err(String::from("--verify needs --replay"));
+    }
+
+    if options.players == Some(0) {
+      return Err(String::from("--players needs at least one player"));
+    }
+
+    if let Some(name) = &options.bot {
+      if controller::bot(name).is_none() {
+        return Err(format!(
+          "unknown bot: {} (choose from {})",
+          name,
+          controller::BOT_NAMES.join(", ")
+        ));
+      }
+    }
+
+    Ok(options)
+  }
+}
+
+fn value<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+  let value = value.ok_or(format!("{} needs a value", flag))?;
+  value
+    .parse()
+    .map_err(|_| format!("invalid value for {}: {}", flag, value))
+}
@@ -1,19 +1,49 @@
-use dynamo_lib::geometry::quad::Quad;
+use crate::board::Cell;
+
+/// What eating a pellet does, on top of the points it's worth.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PelletKind {
+  /// Grows the snake.
+  Normal,
+  /// Grows the snake like a normal pellet but is meant to be worth more and
+  /// not to last.
+  Bonus,
+  /// Takes segments off the snake and is meant to cost points.
+  Poison,
+  /// Takes segments off the snake.
+  Shrink,
+  /// Grows the snake and changes the game's speed for a while.
+  Speed,
+}
+
+impl PelletKind {
+  pub const ALL: [PelletKind; 5] = [
+    PelletKind::Normal,
+    PelletKind::Bonus,
+    PelletKind::Poison,
+    PelletKind::Shrink,
+    PelletKind::Speed,
+  ];
+}
 
 pub struct Pellet {
-  pub quad: Quad,
-  pub visible: bool,
+  pub cell: Cell,
+  pub kind: PelletKind,
+  /// The tick an uneaten pellet disappears on, `None` if it stays until it's
+  /// eaten.
+  pub expires: Option<u64>,
 }
 
 impl Pellet {
-  pub fn new(position: cgmath::Vector2<f32>, radius: f32) -> Pellet {
+  pub fn new(cell: Cell) -> Pellet {
     Pellet {
-      quad: Quad::new(position, (radius, radius).into()),
-      visible: false,
+      cell,
+      kind: PelletKind::Normal,
+      expires: None,
     }
   }
 
-  pub fn update_position(&mut self, position: cgmath::Vector2<f32>) {
-    self.quad = Quad::new(position, self.quad.size);
+  pub fn update_position(&mut self, cell: Cell) {
+    self.cell = cell;
   }
 }
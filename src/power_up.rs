@@ -0,0 +1,50 @@
+use crate::board::Cell;
+
+/// A temporary ability a snake gets by picking up a power-up.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PowerUpKind {
+  /// The snake passes through its own body.
+  Ghost,
+  /// The game runs slower.
+  Slow,
+  /// Pellets close to the head are pulled towards it.
+  Magnet,
+  /// The next wall or border the snake runs into stops it instead of
+  /// crashing it.
+  Shield,
+}
+
+impl PowerUpKind {
+  pub const ALL: [PowerUpKind; 4] = [
+    PowerUpKind::Ghost,
+    PowerUpKind::Slow,
+    PowerUpKind::Magnet,
+    PowerUpKind::Shield,
+  ];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      PowerUpKind::Ghost => "Ghost",
+      PowerUpKind::Slow => "Slow",
+      PowerUpKind::Magnet => "Magnet",
+      PowerUpKind::Shield => "Shield",
+    }
+  }
+}
+
+/// A power-up waiting on the board to be picked up.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PowerUp {
+  pub cell: Cell,
+  pub kind: PowerUpKind,
+  /// The tick it disappears on if nobody picks it up.
+  pub expires: u64,
+}
+
+/// A power-up a snake has picked up, until it wears off.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Effect {
+  pub kind: PowerUpKind,
+  /// The tick it wears off on.
+  pub until: u64,
+}
@@ -0,0 +1,261 @@
+use crate::file::FileError;
+use crate::level::Level;
+use crate::rules::Rules;
+use crate::simulation::Simulation;
+use crate::util::Direction;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const VERSION: u32 = 1;
+
+/// Everything needed to play a game back exactly: how it was set up and the
+/// direction the snake took whenever it changed.
+///
+/// Stored as TOML, with the turns packed into one string of
+/// `<tick><direction>` pairs such as `"0U 7L 14D"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+  pub version: u32,
+  pub seed: u64,
+  /// Ticks the game lasted.
+  pub ticks: u64,
+  /// Direction changes in tick order.
+  #[serde(with = "turns")]
+  pub turns: Vec<(u64, Direction)>,
+  /// The level played on, or `None` for the plain board from `rules`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub level: Option<Level>,
+  pub rules: Rules,
+}
+
+impl Replay {
+  pub fn new(seed: u64, rules: Rules, level: Option<Level>) -> Self {
+    Self {
+      version: VERSION,
+      seed,
+      ticks: 0,
+      turns: Vec::new(),
+      level,
+      rules,
+    }
+  }
+
+  /// A new replay, with no turns yet, of a game set up the way this one was.
+  pub fn restarted(&self) -> Replay {
+    Replay::new(self.seed, self.rules.clone(), self.level.clone())
+  }
+
+  /// A fresh game set up the way this replay's was.
+  pub fn simulation(&self) -> Simulation {
+    self.simulation_for(1)
+  }
+
+  /// A fresh game for `players` snakes set up the way this replay's was.
+  pub fn simulation_for(&self, players: usize) -> Simulation {
+    let level = match &self.level {
+      Some(level) => level.clone(),
+      None => Level::from_rules(&self.rules),
+    };
+    Simulation::with_players(self.rules.clone(), level, players, self.seed)
+  }
+
+  pub fn record(&mut self, tick: u64, direction: Direction) {
+    self.turns.push((tick, direction));
+  }
+
+  /// The turns to feed into the step for `tick`.
+  pub fn turns_at(&self, tick: u64) -> Vec<Direction> {
+    let start = self.turns.partition_point(|(t, _)| *t < tick);
+    self.turns[start..]
+      .iter()
+      .take_while(|(t, _)| *t == tick)
+      .map(|(_, direction)| *direction)
+      .collect()
+  }
+
+  /// Whether a game being played back from this replay has reached its end.
+  pub fn finished(&self, simulation: &Simulation) -> bool {
+    simulation.game_over() || simulation.tick >= self.ticks
+  }
+
+  /// Plays the whole game back without rendering it.
+  pub fn run(&self) -> Simulation {
+    let mut simulation = self.simulation();
+
+    while !self.finished(&simulation) {
+      let turns = self.turns_at(simulation.tick);
+      simulation.step(&turns);
+    }
+
+    simulation
+  }
+
+  pub fn load(path: &Path) -> Result<Replay, FileError> {
+    let text = fs::read_to_string(path)?;
+    Replay::parse(&text)
+  }
+
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    fs::write(path, self.to_string())
+  }
+
+  pub fn parse(text: &str) -> Result<Replay, FileError> {
+    let replay: Replay = toml::from_str(text)?;
+    if replay.version > VERSION {
+      return Err(FileError::Invalid(format!(
+        "replay version {} is newer than this game",
+        replay.version
+      )));
+    }
+
+    replay
+      .rules
+      .validate()
+      .map_err(|error| FileError::Invalid(format!("invalid rules: {}", error)))?;
+    Ok(replay)
+  }
+}
+
+impl fmt::Display for Replay {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let text = toml::to_string(self).map_err(|_| fmt::Error)?;
+    write!(f, "{}", text)
+  }
+}
+
+mod turns {
+  use crate::util::Direction;
+  use serde::de::Error;
+  use serde::{Deserialize, Deserializer, Serializer};
+
+  pub fn serialize<S: Serializer>(
+    turns: &[(u64, Direction)],
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    let text: Vec<String> = turns
+      .iter()
+      .map(|(tick, direction)| format!("{}{}", tick, direction.letter()))
+      .collect();
+    serializer.serialize_str(&text.join(" "))
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Vec<(u64, Direction)>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    let mut turns: Vec<(u64, Direction)> = Vec::new();
+
+    for turn in text.split_whitespace() {
+      let letter = turn.chars().last().unwrap_or_default();
+      let tick: u64 = turn[..turn.len() - letter.len_utf8()]
+        .parse()
+        .map_err(|_| D::Error::custom(format!("invalid turn: {}", turn)))?;
+      let direction = Direction::from_letter(letter)
+        .ok_or_else(|| D::Error::custom(format!("invalid turn: {}", turn)))?;
+
+      if turns.last().is_some_and(|(last, _)| *last > tick) {
+        return Err(D::Error::custom("turns are out of order"));
+      }
+      turns.push((tick, direction));
+    }
+
+    Ok(turns)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::controller::{Controller, Greedy};
+
+  /// Plays a game with the greedy bot, recording its turns as the game's
+  /// frontends do.
+  fn record(seed: u64) -> (Replay, Simulation) {
+    let mut replay = Replay::new(seed, Rules::default(), None);
+    let mut simulation = replay.simulation();
+    let mut bot = Greedy;
+
+    while !simulation.game_over() && simulation.tick < 2000 {
+      let turns: Vec<Direction> = bot.next_turn(&simulation).into_iter().collect();
+      for turn in turns.iter() {
+        replay.record(simulation.tick, *turn);
+      }
+      simulation.step(&turns);
+    }
+    replay.ticks = simulation.tick;
+
+    (replay, simulation)
+  }
+
+  #[test]
+  fn a_recording_reads_back_the_same() {
+    let (replay, _) = record(7);
+    assert!(!replay.turns.is_empty());
+
+    let parsed = Replay::parse(&replay.to_string()).unwrap();
+    assert_eq!(parsed, replay);
+  }
+
+  #[test]
+  fn a_level_reads_back_the_same() {
+    let text = "name: Box\n#####\n#...#\n#.S.#\n#..1#\n#1..#\n#####\n";
+    let level = Level::parse("", text).unwrap();
+    let mut replay = Replay::new(3, Rules::default(), Some(level));
+    replay.record(0, Direction::Left);
+    replay.ticks = 4;
+
+    let parsed = Replay::parse(&replay.to_string()).unwrap();
+    assert_eq!(parsed, replay);
+  }
+
+  #[test]
+  fn playing_back_matches_the_recorded_game() {
+    let (replay, recorded) = record(42);
+    assert!(recorded.snakes[0].score > 0);
+
+    let parsed = Replay::parse(&replay.to_string()).unwrap();
+    for played in [parsed.run(), parsed.run()].iter() {
+      assert_eq!(played.tick, recorded.tick);
+      assert_eq!(played.crashed, recorded.crashed);
+      assert_eq!(played.snakes[0].score, recorded.snakes[0].score);
+      assert_eq!(played.snakes[0].body, recorded.snakes[0].body);
+      assert_eq!(played.pellets[0].cell, recorded.pellets[0].cell);
+    }
+  }
+
+  #[test]
+  fn a_newer_replay_is_turned_away() {
+    let mut replay = Replay::new(1, Rules::default(), None);
+    replay.version = VERSION + 1;
+
+    match Replay::parse(&replay.to_string()) {
+      Err(FileError::Invalid(message)) => assert!(message.contains("newer")),
+      result => panic!("expected a version error, got {:?}", result),
+    }
+  }
+
+  #[test]
+  fn a_replay_with_impossible_rules_is_turned_away() {
+    let broken = [
+      Rules {
+        start_length: 0,
+        ..Default::default()
+      },
+      Rules {
+        board_width: 0,
+        ..Default::default()
+      },
+    ];
+
+    for rules in broken.iter() {
+      let replay = Replay::new(1, rules.clone(), None);
+      assert!(matches!(
+        Replay::parse(&replay.to_string()),
+        Err(FileError::Invalid(_))
+      ));
+    }
+  }
+}
@@ -0,0 +1,614 @@
+use crate::file::{self, FileError};
+use crate::pellet::PelletKind;
+use crate::power_up::PowerUpKind;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// The widest and tallest board a rule set or level can ask for.
+pub(crate) const MAX_BOARD_SIZE: i32 = 200;
+
+/// The most pellets a rule set can keep on the board at once.
+const MAX_PELLET_COUNT: u32 = 100;
+
+/// The fastest tick rate, in ticks per second, that a rule set's speed curve
+/// can reach.
+const MAX_TICK_RATE: f32 = 100.0;
+
+/// The most that a speed pellet or the slow power-up can multiply the tick
+/// rate by.
+const MAX_SPEED_FACTOR: f32 = 4.0;
+
+/// The most mice and chasers a rule set can start a game with, together.
+const MAX_CREATURES: u32 = 100;
+
+/// What happens when the snake runs off the edge of the board.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Borders {
+  /// The board is ringed by walls.
+  Deadly,
+  /// The snake comes back in on the opposite side.
+  Wrap,
+}
+
+/// How the tick rate grows as the snake eats.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeedCurve {
+  /// Ticks per second at the start of a game.
+  pub start: f32,
+  /// Ticks per second added for each pellet eaten.
+  pub increase: f32,
+  pub max: f32,
+}
+
+impl Default for SpeedCurve {
+  fn default() -> Self {
+    Self {
+      start: 15.0,
+      increase: 1.125,
+      max: 40.0,
+    }
+  }
+}
+
+impl SpeedCurve {
+  pub fn tick_rate(&self, score: u32) -> f32 {
+    (self.start + self.increase * score as f32).min(self.max)
+  }
+}
+
+/// How pellets of one kind spawn and what eating one does.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct PelletSettings {
+  /// Chance of a new pellet being this kind, relative to the other kinds'
+  /// weights. 0 never spawns it.
+  pub weight: u32,
+  /// Score for eating it. A negative score takes points away.
+  pub points: i32,
+  /// Ticks an uneaten pellet lasts before it turns up somewhere else. 0
+  /// lasts until it's eaten.
+  pub lifetime: u32,
+  /// Segments that poison and shrink pellets take off the snake.
+  pub segments: u32,
+  /// What speed pellets multiply the tick rate by.
+  pub speed: f32,
+  /// Ticks a speed pellet's change lasts.
+  pub duration: u32,
+}
+
+impl Default for PelletSettings {
+  fn default() -> Self {
+    Self {
+      weight: 0,
+      points: 1,
+      lifetime: 0,
+      segments: 3,
+      speed: 1.5,
+      duration: 75,
+    }
+  }
+}
+
+/// The settings for each kind of pellet. Only normal pellets spawn unless
+/// another kind is given a weight.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "PelletsFile")]
+pub struct Pellets {
+  pub normal: PelletSettings,
+  pub bonus: PelletSettings,
+  pub poison: PelletSettings,
+  pub shrink: PelletSettings,
+  pub speed: PelletSettings,
+}
+
+impl Default for Pellets {
+  fn default() -> Self {
+    let settings = PelletSettings::default();
+
+    Self {
+      normal: PelletSettings {
+        weight: 1,
+        ..settings
+      },
+      bonus: PelletSettings {
+        points: 5,
+        lifetime: 60,
+        ..settings
+      },
+      poison: PelletSettings {
+        points: -2,
+        ..settings
+      },
+      shrink: PelletSettings {
+        points: 0,
+        ..settings
+      },
+      speed: settings,
+    }
+  }
+}
+
+/// A `[pellets.<kind>]` table as written. Anything left out keeps that kind's
+/// default rather than the generic one.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct PelletOverrides {
+  weight: Option<u32>,
+  points: Option<i32>,
+  lifetime: Option<u32>,
+  segments: Option<u32>,
+  speed: Option<f32>,
+  duration: Option<u32>,
+}
+
+impl PelletOverrides {
+  fn apply(self, settings: PelletSettings) -> PelletSettings {
+    PelletSettings {
+      weight: self.weight.unwrap_or(settings.weight),
+      points: self.points.unwrap_or(settings.points),
+      lifetime: self.lifetime.unwrap_or(settings.lifetime),
+      segments: self.segments.unwrap_or(settings.segments),
+      speed: self.speed.unwrap_or(settings.speed),
+      duration: self.duration.unwrap_or(settings.duration),
+    }
+  }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct PelletsFile {
+  normal: PelletOverrides,
+  bonus: PelletOverrides,
+  poison: PelletOverrides,
+  shrink: PelletOverrides,
+  speed: PelletOverrides,
+}
+
+impl From<PelletsFile> for Pellets {
+  fn from(file: PelletsFile) -> Self {
+    let pellets = Pellets::default();
+
+    Self {
+      normal: file.normal.apply(pellets.normal),
+      bonus: file.bonus.apply(pellets.bonus),
+      poison: file.poison.apply(pellets.poison),
+      shrink: file.shrink.apply(pellets.shrink),
+      speed: file.speed.apply(pellets.speed),
+    }
+  }
+}
+
+impl Pellets {
+  pub fn settings(&self, kind: PelletKind) -> &PelletSettings {
+    match kind {
+      PelletKind::Normal => &self.normal,
+      PelletKind::Bonus => &self.bonus,
+      PelletKind::Poison => &self.poison,
+      PelletKind::Shrink => &self.shrink,
+      PelletKind::Speed => &self.speed,
+    }
+  }
+
+  /// The weights of every kind added up, or `None` if they don't fit in a
+  /// `u32`.
+  pub fn total_weight(&self) -> Option<u32> {
+    PelletKind::ALL.iter().try_fold(0u32, |total, kind| {
+      total.checked_add(self.settings(*kind).weight)
+    })
+  }
+}
+
+/// How often one kind of power-up turns up and how long it lasts once
+/// picked up.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerUpSettings {
+  /// Chance of a new power-up being this kind, relative to the other kinds'
+  /// weights. 0 never spawns it.
+  pub weight: u32,
+  /// Ticks the effect lasts.
+  pub duration: u32,
+}
+
+impl Default for PowerUpSettings {
+  fn default() -> Self {
+    Self {
+      weight: 1,
+      duration: 100,
+    }
+  }
+}
+
+/// When power-ups appear and what they do.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerUps {
+  /// Ticks from one power-up being picked up or disappearing to the next one
+  /// appearing. 0 turns power-ups off.
+  pub interval: u32,
+  /// Ticks a power-up stays on the board if nobody picks it up.
+  pub lifetime: u32,
+  /// What slow motion multiplies the tick rate by.
+  pub slow_factor: f32,
+  /// How many cells away from the head a magnet reaches.
+  pub magnet_range: u32,
+  pub ghost: PowerUpSettings,
+  pub slow: PowerUpSettings,
+  pub magnet: PowerUpSettings,
+  pub shield: PowerUpSettings,
+}
+
+impl Default for PowerUps {
+  fn default() -> Self {
+    Self {
+      interval: 0,
+      lifetime: 100,
+      slow_factor: 0.5,
+      magnet_range: 5,
+      ghost: PowerUpSettings::default(),
+      slow: PowerUpSettings::default(),
+      magnet: PowerUpSettings::default(),
+      shield: PowerUpSettings::default(),
+    }
+  }
+}
+
+impl PowerUps {
+  pub fn settings(&self, kind: PowerUpKind) -> &PowerUpSettings {
+    match kind {
+      PowerUpKind::Ghost => &self.ghost,
+      PowerUpKind::Slow => &self.slow,
+      PowerUpKind::Magnet => &self.magnet,
+      PowerUpKind::Shield => &self.shield,
+    }
+  }
+
+  /// The weights of every kind added up, or `None` if they don't fit in a
+  /// `u32`.
+  pub fn total_weight(&self) -> Option<u32> {
+    PowerUpKind::ALL.iter().try_fold(0u32, |total, kind| {
+      total.checked_add(self.settings(*kind).weight)
+    })
+  }
+
+  /// Whether power-ups of `kind` can turn up at all.
+  pub fn spawns(&self, kind: PowerUpKind) -> bool {
+    self.interval > 0 && self.settings(kind).weight > 0
+  }
+}
+
+/// Creatures put on the board at random when a game starts. Patrols come
+/// from the level instead.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Creatures {
+  pub mice: u32,
+  /// Score for catching a mouse.
+  pub mouse_points: u32,
+  pub chasers: u32,
+  /// Ticks between a patrol's moves.
+  pub patrol_interval: u32,
+  /// Ticks between a mouse's moves.
+  pub mouse_interval: u32,
+  /// Ticks between a chaser's moves.
+  pub chaser_interval: u32,
+}
+
+impl Default for Creatures {
+  fn default() -> Self {
+    Self {
+      mice: 0,
+      mouse_points: 3,
+      chasers: 0,
+      patrol_interval: 2,
+      mouse_interval: 2,
+      chaser_interval: 3,
+    }
+  }
+}
+
+/// A rule set, read from a TOML file. Anything left out of the file keeps
+/// its classic value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+  pub name: String,
+  pub board_width: i32,
+  pub board_height: i32,
+  pub borders: Borders,
+  pub start_length: u32,
+  pub growth_per_pellet: u32,
+  /// Pellets kept on the board at once.
+  pub pellet_count: u32,
+  /// Ticks before a pellet that was eaten or disappeared is replaced.
+  pub respawn_delay: u32,
+  /// Pellets in the whole game, 0 for no limit. Eating them all clears the
+  /// board and wins the game.
+  pub total_pellets: u32,
+  pub speed: SpeedCurve,
+  pub pellets: Pellets,
+  pub power_ups: PowerUps,
+  pub creatures: Creatures,
+}
+
+impl Default for Rules {
+  fn default() -> Self {
+    Self {
+      name: String::from("Classic"),
+      board_width: 50,
+      board_height: 50,
+      borders: Borders::Deadly,
+      start_length: 1,
+      growth_per_pellet: 1,
+      pellet_count: 1,
+      respawn_delay: 0,
+      total_pellets: 0,
+      speed: SpeedCurve::default(),
+      pellets: Pellets::default(),
+      power_ups: PowerUps::default(),
+      creatures: Creatures::default(),
+    }
+  }
+}
+
+impl Rules {
+  /// The rule sets that are always available.
+  pub fn presets() -> Vec<Rules> {
+    let pellets = Pellets::default();
+
+    vec![
+      Rules::default(),
+      Rules {
+        name: String::from("Wrap Around"),
+        borders: Borders::Wrap,
+        ..Default::default()
+      },
+      Rules {
+        name: String::from("Steady"),
+        speed: SpeedCurve {
+          increase: 0.0,
+          ..Default::default()
+        },
+        ..Default::default()
+      },
+      Rules {
+        name: String::from("Party"),
+        pellets: Pellets {
+          normal: PelletSettings {
+            weight: 6,
+            ..pellets.normal
+          },
+          bonus: PelletSettings {
+            weight: 2,
+            ..pellets.bonus
+          },
+          poison: PelletSettings {
+            weight: 1,
+            ..pellets.poison
+          },
+          shrink: PelletSettings {
+            weight: 1,
+            ..pellets.shrink
+          },
+          speed: PelletSettings {
+            weight: 1,
+            ..pellets.speed
+          },
+        },
+        power_ups: PowerUps {
+          interval: 150,
+          ..Default::default()
+        },
+        ..Default::default()
+      },
+    ]
+  }
+
+  /// The presets followed by the rule sets in the `rules` directory. Files
+  /// that fail to load are reported and skipped.
+  pub fn available() -> Vec<Rules> {
+    let mut rule_sets = Rules::presets();
+
+    for result in Rules::load_dir(Path::new("rules")) {
+      match result {
+        Ok(rules) => rule_sets.push(rules),
+        Err((path, error)) => eprintln!("Couldn't load rules from {}: {}", path, error),
+      }
+    }
+
+    rule_sets
+  }
+
+  /// The available rule set called `name`, ignoring case, or else the rule
+  /// set in the file at `name`.
+  pub fn find(name: &str) -> Result<Rules, FileError> {
+    let named = Rules::presets()
+      .into_iter()
+      .chain(Rules::load_dir(Path::new("rules")).into_iter().flatten())
+      .find(|rules| rules.name.eq_ignore_ascii_case(name));
+
+    match named {
+      Some(rules) => Ok(rules),
+      None => Rules::load(Path::new(name)),
+    }
+  }
+
+  pub fn load(path: &Path) -> Result<Rules, FileError> {
+    let text = fs::read_to_string(path)?;
+    Rules::parse(&text)
+  }
+
+  pub fn parse(text: &str) -> Result<Rules, FileError> {
+    let rules: Rules = toml::from_str(text)?;
+    rules.validate()?;
+    Ok(rules)
+  }
+
+  /// Every `.toml` file in `dir`, sorted by file name. Files that fail to
+  /// load are reported alongside their path instead.
+  pub fn load_dir(dir: &Path) -> Vec<Result<Rules, (String, FileError)>> {
+    file::load_dir(dir, "toml", Rules::load)
+  }
+
+  /// Checks that a game can be played under these rules.
+  pub(crate) fn validate(&self) -> Result<(), FileError> {
+    if self.board_width < 5 || self.board_height < 5 {
+      return Err(FileError::Invalid(String::from(
+        "the board must be at least 5 by 5",
+      )));
+    }
+    if self.board_width > MAX_BOARD_SIZE || self.board_height > MAX_BOARD_SIZE {
+      return Err(FileError::Invalid(format!(
+        "the board can't be bigger than {} by {}",
+        MAX_BOARD_SIZE, MAX_BOARD_SIZE
+      )));
+    }
+    if self.start_length == 0 {
+      return Err(FileError::Invalid(String::from(
+        "start_length must be at least 1",
+      )));
+    }
+    if self.pellet_count == 0 || self.pellet_count > MAX_PELLET_COUNT {
+      return Err(FileError::Invalid(format!(
+        "pellet_count must be from 1 to {}",
+        MAX_PELLET_COUNT
+      )));
+    }
+    let speed = &self.speed;
+    if ![speed.start, speed.max]
+      .iter()
+      .all(|rate| *rate > 0.0 && *rate <= MAX_TICK_RATE)
+    {
+      return Err(FileError::Invalid(format!(
+        "speed.start and speed.max must be above 0 and at most {}",
+        MAX_TICK_RATE
+      )));
+    }
+    if !(speed.increase >= 0.0 && speed.increase.is_finite()) {
+      return Err(FileError::Invalid(String::from(
+        "speed.increase can't be negative",
+      )));
+    }
+    match self.pellets.total_weight() {
+      Some(0) => {
+        return Err(FileError::Invalid(String::from(
+          "at least one kind of pellet needs a weight",
+        )))
+      }
+      None => {
+        return Err(FileError::Invalid(format!(
+          "pellet weights can't add up to more than {}",
+          u32::MAX
+        )))
+      }
+      Some(_) => {}
+    }
+    let factor = self.pellets.speed.speed;
+    if !(factor > 0.0 && factor <= MAX_SPEED_FACTOR) {
+      return Err(FileError::Invalid(format!(
+        "pellets.speed.speed must be above 0 and at most {}",
+        MAX_SPEED_FACTOR
+      )));
+    }
+    let creatures = &self.creatures;
+    if creatures.mice.saturating_add(creatures.chasers) > MAX_CREATURES {
+      return Err(FileError::Invalid(format!(
+        "creatures.mice and creatures.chasers can't add up to more than {}",
+        MAX_CREATURES
+      )));
+    }
+    if creatures.patrol_interval == 0
+      || creatures.mouse_interval == 0
+      || creatures.chaser_interval == 0
+    {
+      return Err(FileError::Invalid(String::from(
+        "creature intervals must be at least 1",
+      )));
+    }
+    if self.power_ups.interval > 0 {
+      match self.power_ups.total_weight() {
+        Some(0) => {
+          return Err(FileError::Invalid(String::from(
+            "at least one kind of power-up needs a weight when power_ups.interval is set",
+          )))
+        }
+        None => {
+          return Err(FileError::Invalid(format!(
+            "power-up weights can't add up to more than {}",
+            u32::MAX
+          )))
+        }
+        Some(_) => {}
+      }
+      if self.power_ups.lifetime == 0 {
+        return Err(FileError::Invalid(String::from(
+          "power_ups.lifetime must be positive",
+        )));
+      }
+      let factor = self.power_ups.slow_factor;
+      if !(factor > 0.0 && factor <= MAX_SPEED_FACTOR) {
+        return Err(FileError::Invalid(format!(
+          "power_ups.slow_factor must be above 0 and at most {}",
+          MAX_SPEED_FACTOR
+        )));
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn the_presets_are_valid() {
+    for rules in Rules::presets() {
+      assert!(rules.validate().is_ok(), "{}", rules.name);
+    }
+  }
+
+  #[test]
+  fn the_bundled_rule_sets_load() {
+    let rule_sets = Rules::load_dir(Path::new("rules"));
+    assert!(!rule_sets.is_empty());
+
+    for result in rule_sets {
+      if let Err((path, error)) = result {
+        panic!("{}: {}", path, error);
+      }
+    }
+  }
+
+  #[test]
+  fn rules_that_would_hang_the_game_are_turned_away() {
+    let texts = [
+      "[speed]\nincrease = -1.0",
+      "[speed]\nincrease = inf",
+      "[speed]\nstart = 1e10\nmax = 1e10",
+      "[speed]\nstart = nan",
+      "[speed]\nmax = inf",
+      "[pellets.speed]\nspeed = 1e10",
+      "[pellets.speed]\nspeed = nan",
+      "[power_ups]\ninterval = 10\nslow_factor = inf\n[power_ups.slow]\nweight = 1",
+      "[power_ups]\ninterval = 10\nslow_factor = 0.0\n[power_ups.slow]\nweight = 1",
+      "board_width = 100000",
+      "board_height = 201",
+      "pellet_count = 4000000000",
+      "[creatures]\nmice = 60\nchasers = 60",
+      "[pellets.normal]\nweight = 4000000000\n[pellets.bonus]\nweight = 4000000000",
+      "[power_ups]\ninterval = 10\n[power_ups.ghost]\nweight = 4000000000\n\
+       [power_ups.slow]\nweight = 4000000000",
+    ];
+
+    for text in texts.iter() {
+      assert!(
+        matches!(Rules::parse(text), Err(FileError::Invalid(_))),
+        "{}",
+        text
+      );
+    }
+  }
+}
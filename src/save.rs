@@ -0,0 +1,191 @@
+use crate::file::{self, FileError};
+use crate::replay::Replay;
+use crate::simulation::Simulation;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const VERSION: u32 = 1;
+
+/// Most ticks a saved game can go without the snake turning. Only a snake
+/// left going straight on a wrapping board lasts anywhere near this long, and
+/// it keeps a corrupt save from being played back for ever on resuming.
+const MAX_IDLE_TICKS: u64 = 10_000;
+
+/// A single-player game left in progress, to be picked up later.
+///
+/// The game is kept as a replay of its turns so far. Resuming plays them back,
+/// which puts the snake, pellet, score and random number generator exactly
+/// where they were. The score and length are stored as well so a save that
+/// doesn't play back to the same game is caught.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveGame {
+  pub version: u32,
+  pub score: u32,
+  pub length: u32,
+  pub game: Replay,
+}
+
+/// Just the version, read first so a save from a newer game is turned away
+/// before the rest of it is looked at.
+#[derive(Deserialize)]
+struct Header {
+  version: u32,
+}
+
+impl SaveGame {
+  /// Where the save lives in the user's data directory.
+  pub fn default_path() -> Option<PathBuf> {
+    file::user_path(dirs::data_dir(), "save.toml")
+  }
+
+  /// Saves `simulation`, whose turns so far are in `recording`.
+  pub fn new(recording: &Replay, simulation: &Simulation) -> Self {
+    let snake = &simulation.snakes[0];
+    let mut game = recording.clone();
+    game.ticks = simulation.tick;
+
+    Self {
+      version: VERSION,
+      score: snake.score,
+      length: snake.body.len() as u32,
+      game,
+    }
+  }
+
+  /// Loads the save at `path`, or `None` if there isn't one.
+  pub fn load(path: &Path) -> Result<Option<SaveGame>, FileError> {
+    file::load(path, SaveGame::parse)
+  }
+
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    file::save(self, path)
+  }
+
+  /// Deletes the save at `path`, if there is one.
+  pub fn remove(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+      Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+      result => result,
+    }
+  }
+
+  pub fn parse(text: &str) -> Result<SaveGame, FileError> {
+    let header: Header = toml::from_str(text)?;
+    if header.version > VERSION {
+      return Err(FileError::Invalid(format!(
+        "save version {} is newer than this game",
+        header.version
+      )));
+    }
+
+    let save: SaveGame = toml::from_str(text)?;
+    save
+      .game
+      .rules
+      .validate()
+      .map_err(|error| FileError::Invalid(format!("invalid rules: {}", error)))?;
+
+    let mut last_turn = 0;
+    for tick in save
+      .game
+      .turns
+      .iter()
+      .map(|(tick, _)| *tick)
+      .chain(Some(save.game.ticks))
+    {
+      if tick.saturating_sub(last_turn) > MAX_IDLE_TICKS {
+        return Err(FileError::Invalid(format!(
+          "the saved game goes {} ticks without a turn",
+          tick - last_turn
+        )));
+      }
+      last_turn = tick;
+    }
+
+    Ok(save)
+  }
+
+  /// Plays the saved game back to where it was left.
+  pub fn resume(&self) -> Result<Simulation, FileError> {
+    let mut simulation = self.game.simulation();
+    while simulation.tick < self.game.ticks && !simulation.game_over() {
+      let turns = self.game.turns_at(simulation.tick);
+      simulation.step(&turns);
+    }
+
+    let snake = &simulation.snakes[0];
+    if simulation.game_over() {
+      return Err(FileError::Invalid(String::from(
+        "the saved game had already ended",
+      )));
+    }
+    if snake.score != self.score || snake.body.len() as u32 != self.length {
+      return Err(FileError::Invalid(String::from(
+        "the saved game doesn't play back to where it was left",
+      )));
+    }
+
+    Ok(simulation)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::rules::Rules;
+  use crate::util::Direction;
+
+  /// A game a few ticks in, with one turn taken.
+  fn saved() -> SaveGame {
+    let mut recording = Replay::new(11, Rules::default(), None);
+    let mut simulation = recording.simulation();
+    for _ in 0..3 {
+      simulation.step(&[]);
+    }
+    recording.record(simulation.tick, Direction::Up);
+    simulation.step(&[Direction::Up]);
+    simulation.step(&[]);
+
+    SaveGame::new(&recording, &simulation)
+  }
+
+  fn reparse(save: &SaveGame) -> Result<SaveGame, FileError> {
+    SaveGame::parse(&toml::to_string(save).unwrap())
+  }
+
+  #[test]
+  fn a_save_resumes_where_it_was_left() {
+    let save = saved();
+    let parsed = reparse(&save).unwrap();
+    assert_eq!(parsed, save);
+
+    let simulation = parsed.resume().unwrap();
+    assert_eq!(simulation.tick, save.game.ticks);
+    assert_eq!(simulation.snakes[0].direction, Direction::Up);
+  }
+
+  #[test]
+  fn a_save_with_impossible_rules_is_turned_away() {
+    let mut save = saved();
+    save.game.rules.start_length = 0;
+
+    match reparse(&save) {
+      Err(FileError::Invalid(message)) => assert!(message.contains("start_length")),
+      result => panic!("expected the rules to be turned away, got {:?}", result),
+    }
+  }
+
+  #[test]
+  fn a_save_that_never_ends_is_turned_away() {
+    let mut save = saved();
+    save.game.turns.clear();
+    save.game.ticks = u64::MAX / 2;
+
+    match reparse(&save) {
+      Err(FileError::Invalid(message)) => assert!(message.contains("without a turn")),
+      result => panic!("expected the save to be turned away, got {:?}", result),
+    }
+  }
+}
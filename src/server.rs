@@ -0,0 +1,368 @@
+use crate::net::{ClientMessage, ServerMessage};
+use crate::replay::Replay;
+use crate::rules::Rules;
+use crate::ticker::Ticker;
+use crate::turn_queue::TurnQueue;
+use crate::util::Direction;
+use std::io::{self, BufReader};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError};
+use std::thread;
+use std::time::Duration;
+
+/// Longest to wait for a message before checking whether a tick is due.
+const FRAME_TIME: Duration = Duration::from_millis(2);
+
+/// Messages a connection can fall behind by before it's dropped, a few
+/// seconds of ticks even at top speed.
+const OUTBOX_SIZE: usize = 256;
+
+/// Something that happened on one of the server's connections.
+enum ServerEvent {
+  Joined(TcpStream),
+  Message(usize, ClientMessage),
+  Left(usize),
+}
+
+struct Connection {
+  id: usize,
+  stream: TcpStream,
+  address: String,
+  /// Messages for the connection's writer thread to send, so a client that
+  /// stops reading doesn't hold up the others.
+  outbox: SyncSender<ServerMessage>,
+  /// Wants to play in the next round.
+  ready: bool,
+  /// The snake this connection steers in the current round.
+  player: Option<usize>,
+}
+
+/// Runs networked games: waits for players to connect, then plays rounds
+/// between them, sending every client each tick's turns.
+pub struct Server {
+  players: usize,
+  fixed_seed: Option<u64>,
+  rules: Rules,
+  address: SocketAddr,
+  events: Receiver<ServerEvent>,
+  sender: Sender<ServerEvent>,
+  connections: Vec<Connection>,
+  next_id: usize,
+  rounds: u32,
+}
+
+impl Server {
+  /// Starts listening on `address` for rounds of `players` players under
+  /// `rules`, each seeded with `seed` or a random seed if that's `None`.
+  pub fn bind(
+    address: impl ToSocketAddrs,
+    players: usize,
+    seed: Option<u64>,
+    rules: Rules,
+  ) -> io::Result<Server> {
+    let listener = TcpListener::bind(address)?;
+    let address = listener.local_addr()?;
+
+    let (sender, events) = mpsc::channel();
+    let joined = sender.clone();
+    thread::spawn(move || {
+      for stream in listener.incoming().flatten() {
+        if joined.send(ServerEvent::Joined(stream)).is_err() {
+          break;
+        }
+      }
+    });
+
+    Ok(Server {
+      players,
+      fixed_seed: seed,
+      rules,
+      address,
+      events,
+      sender,
+      connections: Vec::new(),
+      next_id: 0,
+      rounds: 0,
+    })
+  }
+
+  /// The address the server is listening on.
+  pub fn local_addr(&self) -> SocketAddr {
+    self.address
+  }
+
+  pub fn players(&self) -> usize {
+    self.players
+  }
+
+  pub fn run(&mut self) {
+    loop {
+      while !self.lobby_full() {
+        match self.events.recv() {
+          Ok(event) => self.handle(event, &mut []),
+          Err(_) => return,
+        }
+      }
+
+      self.play_round();
+    }
+  }
+
+  /// Enough players are connected and all of them are ready.
+  fn lobby_full(&self) -> bool {
+    self.connections.len() >= self.players
+      && self.connections.iter().all(|connection| connection.ready)
+  }
+
+  fn play_round(&mut self) {
+    self.rounds += 1;
+    let seed = self.fixed_seed.unwrap_or_else(rand::random);
+    let setup = Replay::new(seed, self.rules.clone(), None);
+    let players = self.connections.len();
+
+    let mut simulation = setup.simulation_for(players);
+    let mut turns: Vec<TurnQueue> = (0..players).map(|_| TurnQueue::new()).collect();
+
+    for (player, connection) in self.connections.iter_mut().enumerate() {
+      connection.ready = false;
+      connection.player = Some(player);
+    }
+    self.broadcast(|connection| {
+      connection.player.map(|player| ServerMessage::Game {
+        player,
+        players,
+        setup: Box::new(setup.clone()),
+      })
+    });
+    println!(
+      "round {}: {} players, {} rules, seed {}",
+      self.rounds, players, self.rules.name, seed
+    );
+
+    let mut ticker = Ticker::new();
+    while !simulation.game_over() && !self.connections.is_empty() {
+      match self.events.recv_timeout(FRAME_TIME) {
+        Ok(event) => self.handle(event, &mut turns),
+        Err(RecvTimeoutError::Timeout) => {}
+        Err(RecvTimeoutError::Disconnected) => return,
+      }
+
+      for _ in 0..ticker.update(simulation.tick_rate()) {
+        let chosen: Vec<Direction> = simulation
+          .snakes
+          .iter()
+          .zip(turns.iter_mut())
+          .map(|(snake, turns)| turns.next_turn(snake.travelled).unwrap_or(Direction::None))
+          .collect();
+
+        let step: Vec<&[Direction]> = chosen.iter().map(std::slice::from_ref).collect();
+        simulation.step_players(&step);
+        self.broadcast(|connection| {
+          connection
+            .player
+            .map(|_| ServerMessage::Tick(chosen.clone()))
+        });
+
+        if simulation.game_over() {
+          break;
+        }
+      }
+    }
+
+    let result = match simulation.winner() {
+      Some(player) => format!("player {} wins", player + 1),
+      None => String::from("draw"),
+    };
+    let scores: Vec<String> = simulation
+      .snakes
+      .iter()
+      .map(|snake| snake.score.to_string())
+      .collect();
+    println!(
+      "round {}: {} after {} ticks, scores {}",
+      self.rounds,
+      result,
+      simulation.tick,
+      scores.join(" ")
+    );
+
+    for connection in self.connections.iter_mut() {
+      connection.player = None;
+    }
+  }
+
+  fn handle(&mut self, event: ServerEvent, turns: &mut [TurnQueue]) {
+    match event {
+      ServerEvent::Joined(mut stream) => {
+        let address = stream
+          .peer_addr()
+          .map_or_else(|_| String::from("unknown"), |address| address.to_string());
+        if self.connections.len() >= self.players {
+          println!("{} turned away, the server is full", address);
+          let _ = ServerMessage::Full.write(&mut stream);
+          return;
+        }
+
+        match self.listen(&stream) {
+          Ok((id, outbox)) => {
+            println!("{} joined", address);
+            self.connections.push(Connection {
+              id,
+              stream,
+              address,
+              outbox,
+              ready: false,
+              player: None,
+            });
+          }
+          Err(error) => eprintln!("Couldn't set up connection from {}: {}", address, error),
+        }
+      }
+      ServerEvent::Message(id, message) => {
+        let connection = match self.connections.iter_mut().find(|c| c.id == id) {
+          Some(connection) => connection,
+          None => return,
+        };
+
+        match message {
+          ClientMessage::Ready => connection.ready = true,
+          ClientMessage::Unready => connection.ready = false,
+          ClientMessage::Turn(direction) => {
+            if let Some(turns) = connection.player.and_then(|player| turns.get_mut(player)) {
+              turns.push(direction);
+            }
+          }
+        }
+      }
+      ServerEvent::Left(id) => self.disconnect(id),
+    }
+  }
+
+  /// Reads the messages a new connection sends and writes the ones it's sent,
+  /// each on a thread of its own. Returns the connection's id and where to
+  /// put messages for it.
+  fn listen(&mut self, stream: &TcpStream) -> io::Result<(usize, SyncSender<ServerMessage>)> {
+    stream.set_nodelay(true)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream.try_clone()?;
+    let id = self.next_id;
+    self.next_id += 1;
+
+    let sender = self.sender.clone();
+    thread::spawn(move || loop {
+      let event = match ClientMessage::read(&mut reader) {
+        Ok(Some(message)) => ServerEvent::Message(id, message),
+        Ok(None) | Err(_) => ServerEvent::Left(id),
+      };
+
+      let left = matches!(event, ServerEvent::Left(_));
+      if sender.send(event).is_err() || left {
+        break;
+      }
+    });
+
+    let (outbox, messages) = mpsc::sync_channel::<ServerMessage>(OUTBOX_SIZE);
+    let sender = self.sender.clone();
+    thread::spawn(move || {
+      for message in messages {
+        if message.write(&mut writer).is_err() {
+          let _ = sender.send(ServerEvent::Left(id));
+          break;
+        }
+      }
+    });
+
+    Ok((id, outbox))
+  }
+
+  /// Queues every connection its message, if it has one, dropping any that
+  /// has gone or fallen too far behind.
+  fn broadcast<F: Fn(&Connection) -> Option<ServerMessage>>(&mut self, message: F) {
+    let mut lost = Vec::new();
+    for connection in self.connections.iter() {
+      let message = match message(connection) {
+        Some(message) => message,
+        None => continue,
+      };
+      match connection.outbox.try_send(message) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => {
+          println!("{} fell too far behind", connection.address);
+          lost.push(connection.id);
+        }
+        Err(TrySendError::Disconnected(_)) => lost.push(connection.id),
+      }
+    }
+
+    for id in lost {
+      self.disconnect(id);
+    }
+  }
+
+  /// Forgets a connection and hangs up on it. Its snake, if it has one, plays
+  /// on without input.
+  fn disconnect(&mut self, id: usize) {
+    if let Some(index) = self.connections.iter().position(|c| c.id == id) {
+      let connection = self.connections.remove(index);
+      let _ = connection.stream.shutdown(Shutdown::Both);
+      println!("{} left", connection.address);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::BufRead;
+
+  /// A server for two players with two connections to it, each as the
+  /// server's id for it and the client's end.
+  fn lobby() -> (Server, Vec<(usize, BufReader<TcpStream>)>) {
+    let mut server = Server::bind(("127.0.0.1", 0), 2, Some(1), Rules::default()).unwrap();
+    let mut clients = Vec::new();
+    for _ in 0..2 {
+      let stream = TcpStream::connect(server.local_addr()).unwrap();
+      let event = server.events.recv().unwrap();
+      server.handle(event, &mut []);
+      let id = server.connections.last().unwrap().id;
+      clients.push((id, BufReader::new(stream)));
+    }
+    (server, clients)
+  }
+
+  #[test]
+  fn a_player_who_backs_out_holds_up_the_round() {
+    let (mut server, clients) = lobby();
+    let (first, second) = (clients[0].0, clients[1].0);
+
+    server.handle(ServerEvent::Message(first, ClientMessage::Ready), &mut []);
+    server.handle(ServerEvent::Message(first, ClientMessage::Unready), &mut []);
+    server.handle(ServerEvent::Message(second, ClientMessage::Ready), &mut []);
+    assert!(!server.lobby_full());
+
+    server.handle(ServerEvent::Message(first, ClientMessage::Ready), &mut []);
+    assert!(server.lobby_full());
+  }
+
+  #[test]
+  fn ticks_only_go_to_players_in_the_round() {
+    let (mut server, mut clients) = lobby();
+    server.connections[0].player = Some(0);
+    server.broadcast(|connection| {
+      connection
+        .player
+        .map(|_| ServerMessage::Tick(vec![Direction::Up]))
+    });
+
+    let reader = &mut clients[0].1;
+    let tick = ServerMessage::read(reader).unwrap();
+    assert!(matches!(tick, Some(ServerMessage::Tick(_))));
+
+    let reader = &mut clients[1].1;
+    let stream = reader.get_ref();
+    stream
+      .set_read_timeout(Some(Duration::from_millis(100)))
+      .unwrap();
+    assert!(reader.fill_buf().is_err());
+  }
+}
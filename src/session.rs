@@ -0,0 +1,135 @@
+use crate::event::Event;
+use crate::level::Level;
+use crate::replay::Replay;
+use crate::rules::Rules;
+use crate::simulation::Simulation;
+use crate::util::Direction;
+use std::io;
+use std::path::Path;
+
+/// A single-player game played locally, either from the turns a player or bot
+/// takes or back from a replay, recorded as it goes.
+///
+/// Every frontend starts and steps its local games through this, so they
+/// play and record them the same way.
+pub struct Session {
+  /// Replay being played back instead of taking turns.
+  pub playback: Option<Replay>,
+  /// The current game's turns so far.
+  pub recording: Replay,
+}
+
+impl Session {
+  pub fn new(playback: Option<Replay>) -> Self {
+    let recording = match &playback {
+      Some(replay) => replay.restarted(),
+      None => Replay::new(0, Rules::default(), None),
+    };
+
+    Self {
+      playback,
+      recording,
+    }
+  }
+
+  /// Sets up a new game for `players` snakes: the one being played back, or
+  /// one under `rules` on `level` seeded with `seed`, or a random seed if
+  /// that's `None`.
+  pub fn start(
+    &mut self,
+    seed: Option<u64>,
+    rules: &Rules,
+    level: Option<&Level>,
+    players: usize,
+  ) -> Simulation {
+    self.recording = match &self.playback {
+      Some(replay) => replay.restarted(),
+      None => Replay::new(
+        seed.unwrap_or_else(rand::random),
+        rules.clone(),
+        level.cloned(),
+      ),
+    };
+    self.recording.simulation_for(players)
+  }
+
+  /// Runs one tick of `simulation`. Unless a replay is being played back,
+  /// `next_turn` is asked for the turn to take. A turn that changes the
+  /// snake's direction is recorded.
+  pub fn step<F>(&mut self, simulation: &mut Simulation, next_turn: F) -> Vec<Event>
+  where
+    F: FnOnce(&Simulation) -> Option<Direction>,
+  {
+    let tick = simulation.tick;
+    let turns: Vec<Direction> = match &self.playback {
+      Some(replay) => replay.turns_at(tick),
+      None => next_turn(simulation).into_iter().collect(),
+    };
+
+    // The turns themselves are recorded rather than the direction after the
+    // tick, which a shield can stop the snake out of.
+    let direction = simulation.snakes[0].direction;
+    for turn in turns.iter() {
+      if *turn != Direction::None && *turn != direction {
+        self.recording.record(tick, *turn);
+      }
+    }
+
+    simulation.step(&turns)
+  }
+
+  /// Whether the game is over, or has reached the end of the replay being
+  /// played back.
+  pub fn finished(&self, simulation: &Simulation) -> bool {
+    match &self.playback {
+      Some(replay) => replay.finished(simulation),
+      None => simulation.game_over(),
+    }
+  }
+
+  /// Writes the replay of the game, which ended at `simulation`, to `path`.
+  pub fn save_recording(&mut self, simulation: &Simulation, path: &Path) -> io::Result<()> {
+    self.recording.ticks = simulation.tick;
+    self.recording.save(path)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::power_up::PowerUpKind;
+
+  /// Starts a game for `session` with a shield that lasts the whole game.
+  fn start_shielded(session: &mut Session) -> Simulation {
+    let mut simulation = session.start(Some(3), &Rules::default(), None, 1);
+    simulation.snakes[0].add_effect(PowerUpKind::Shield, u64::MAX);
+    simulation
+  }
+
+  #[test]
+  fn a_turn_a_shield_stops_plays_back_the_same() {
+    let mut session = Session::new(None);
+    let mut simulation = start_shielded(&mut session);
+
+    while simulation.snakes[0].head().0 > 1 {
+      session.step(&mut simulation, |_| Some(Direction::Left));
+    }
+    session.step(&mut simulation, |_| Some(Direction::Up));
+    session.step(&mut simulation, |_| Some(Direction::Left));
+    assert!(!simulation.snakes[0].has_effect(PowerUpKind::Shield));
+    assert!(!simulation.game_over());
+    for _ in 0..3 {
+      session.step(&mut simulation, |_| Some(Direction::Down));
+    }
+    session.recording.ticks = simulation.tick;
+
+    let mut playback = Session::new(Some(session.recording.clone()));
+    let mut played = start_shielded(&mut playback);
+    while !playback.finished(&played) {
+      playback.step(&mut played, |_| None);
+    }
+
+    assert_eq!(played.tick, simulation.tick);
+    assert_eq!(played.snakes[0].body, simulation.snakes[0].body);
+  }
+}
@@ -0,0 +1,1446 @@
+use crate::board::{Board, Cell};
+use crate::creature::{Creature, CreatureKind};
+use crate::event::Event;
+use crate::level::Level;
+use crate::occupancy::{Occupancy, Tile};
+use crate::pellet::{Pellet, PelletKind};
+use crate::power_up::{PowerUp, PowerUpKind};
+use crate::rules::{Borders, Rules};
+use crate::snake::{Crash, Snake};
+use crate::util::Direction;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+
+const DIRECTIONS: [Direction; 4] = [
+  Direction::Up,
+  Direction::Down,
+  Direction::Left,
+  Direction::Right,
+];
+
+/// How close a snake's head has to get before a mouse runs from it.
+const FLEE_DISTANCE: i32 = 6;
+
+/// How far from every snake's head a mouse or chaser has to appear.
+const SPAWN_DISTANCE: i32 = 8;
+
+/// The game rules and world, independent of any window or renderer.
+///
+/// A frontend feeds the directions the player asked for into `step` and
+/// reacts to the events it returns; everything else about the game lives here.
+pub struct Simulation {
+  pub rules: Rules,
+  pub board: Board,
+  pub walls: Vec<Cell>,
+  /// Pairs of cells joined by a portal. Snakes go through them and never
+  /// stop on them.
+  pub portals: Vec<(Cell, Cell)>,
+  /// Where each patrol starts and the way it sets off.
+  pub patrols: Vec<(Cell, Direction)>,
+  /// Everything besides the snakes that moves.
+  pub creatures: Vec<Creature>,
+  /// One snake per player, player one first.
+  pub snakes: Vec<Snake>,
+  /// The pellets on the board, oldest first.
+  pub pellets: Vec<Pellet>,
+  pub occupancy: Occupancy,
+  /// Snakes crashed until at most one was left going, or the only one did in
+  /// a game for one, which ends the game.
+  pub crashed: bool,
+  /// The snakes filled every free cell or ate every pellet the rules allow.
+  pub won: bool,
+  /// Ticks run since the game started.
+  pub tick: u64,
+  /// Game time since the game started, in seconds.
+  pub elapsed: f32,
+  /// Every random choice in a game comes from `rng`, seeded with this.
+  pub seed: u64,
+  /// The speed change from the last speed pellet eaten, while it lasts.
+  pub boost: Option<SpeedBoost>,
+  /// The power-up waiting on the board, if there is one.
+  pub power_up: Option<PowerUp>,
+  /// The tick the next power-up appears on, when there's none on the board.
+  next_power_up: u64,
+  /// The ticks that pellets waiting to be replaced are due on.
+  respawns: Vec<u64>,
+  rng: Pcg32,
+}
+
+/// A temporary change to the tick rate.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SpeedBoost {
+  /// What the tick rate is multiplied by.
+  pub factor: f32,
+  /// The tick the change wears off on.
+  pub until: u64,
+}
+
+impl Default for Simulation {
+  fn default() -> Self {
+    Self::new(Rules::default(), 0)
+  }
+}
+
+impl Simulation {
+  /// A game under `rules` on the plain board they describe, ready to play.
+  pub fn new(rules: Rules, seed: u64) -> Self {
+    let level = Level::from_rules(&rules);
+    Self::with_level(rules, level, seed)
+  }
+
+  /// A game under `rules` on `level`, ready to play.
+  pub fn with_level(rules: Rules, level: Level, seed: u64) -> Self {
+    Self::with_players(rules, level, 1, seed)
+  }
+
+  /// A game for `players` snakes under `rules` on `level`, ready to play.
+  pub fn with_players(rules: Rules, level: Level, players: usize, seed: u64) -> Self {
+    let board = level.board;
+    let snakes = level
+      .spawns(players)
+      .into_iter()
+      .map(|spawn| Snake::new(spawn, rules.start_length))
+      .collect();
+
+    let mut simulation = Self {
+      board,
+      walls: level.walls,
+      portals: level.portals,
+      patrols: level.patrols,
+      creatures: Vec::new(),
+      snakes,
+      pellets: Vec::new(),
+      occupancy: Occupancy::new(board),
+      crashed: false,
+      won: false,
+      tick: 0,
+      elapsed: 0.0,
+      seed,
+      boost: None,
+      power_up: None,
+      next_power_up: 0,
+      respawns: Vec::new(),
+      rng: Pcg32::seed_from_u64(seed),
+      rules,
+    };
+
+    simulation.reset(seed);
+    simulation
+  }
+
+  /// Starts a new game. Two games with the same `seed` and inputs play out
+  /// identically.
+  pub fn reset(&mut self, seed: u64) {
+    self.seed = seed;
+    self.rng = Pcg32::seed_from_u64(seed);
+    self.crashed = false;
+    self.won = false;
+    self.tick = 0;
+    self.elapsed = 0.0;
+    self.boost = None;
+    self.power_up = None;
+    self.next_power_up = self.rules.power_ups.interval as u64;
+    self.pellets.clear();
+    self.respawns.clear();
+
+    self.occupancy = Occupancy::new(self.board);
+    for cell in self.walls.iter() {
+      self.occupancy.set(*cell, Tile::Wall);
+    }
+    for (a, b) in self.portals.iter() {
+      self.occupancy.set(*a, Tile::Portal);
+      self.occupancy.set(*b, Tile::Portal);
+    }
+    for snake in self.snakes.iter_mut() {
+      snake.reset();
+      for cell in snake.body.iter() {
+        self.occupancy.set(*cell, Tile::Snake);
+      }
+    }
+    self.creatures.clear();
+    for (cell, heading) in self.patrols.iter() {
+      if self.occupancy.get(*cell) == Tile::Empty {
+        self
+          .creatures
+          .push(Creature::new(CreatureKind::Patrol, *cell, *heading));
+        self.occupancy.set(*cell, Tile::Creature);
+      }
+    }
+
+    let count = match self.pellets_left() {
+      Some(left) => left.min(self.rules.pellet_count),
+      None => self.rules.pellet_count,
+    };
+    for _ in 0..count {
+      self.spawn_pellet();
+    }
+    for _ in 0..self.rules.creatures.mice {
+      self.spawn_creature(CreatureKind::Mouse);
+    }
+    for _ in 0..self.rules.creatures.chasers {
+      self.spawn_creature(CreatureKind::Chaser);
+    }
+  }
+
+  pub fn game_over(&self) -> bool {
+    self.crashed || self.won
+  }
+
+  /// The player who won a game for more than one: the last snake still going,
+  /// or the highest score if the board filled up. `None` for a draw or while
+  /// the game is still on.
+  pub fn winner(&self) -> Option<usize> {
+    if !self.game_over() {
+      return None;
+    }
+
+    let standing: Vec<usize> = (0..self.snakes.len())
+      .filter(|i| self.snakes[*i].crash.is_none())
+      .collect();
+    if self.crashed {
+      return match standing.as_slice() {
+        [winner] => Some(*winner),
+        _ => None,
+      };
+    }
+
+    let best = standing.iter().map(|i| self.snakes[*i].score).max()?;
+    match standing
+      .iter()
+      .filter(|i| self.snakes[**i].score == best)
+      .collect::<Vec<_>>()
+      .as_slice()
+    {
+      [winner] => Some(**winner),
+      _ => None,
+    }
+  }
+
+  /// The creature on `cell`, if there is one.
+  pub fn creature_at(&self, cell: Cell) -> Option<&Creature> {
+    self.creatures.iter().find(|creature| creature.cell == cell)
+  }
+
+  /// The pellet on `cell`, if there is one.
+  pub fn pellet_at(&self, cell: Cell) -> Option<&Pellet> {
+    self.pellets.iter().find(|pellet| pellet.cell == cell)
+  }
+
+  /// Pellets still to eat to clear the board, `None` if the rules don't
+  /// limit them.
+  pub fn pellets_left(&self) -> Option<u32> {
+    match self.rules.total_pellets {
+      0 => None,
+      total => Some(total.saturating_sub(self.pellets_eaten())),
+    }
+  }
+
+  fn pellets_eaten(&self) -> u32 {
+    self.snakes.iter().map(|snake| snake.eaten).sum()
+  }
+
+  /// Ticks per second at the current score, which is the best score when
+  /// there is more than one snake, changed by any speed pellet or slow
+  /// motion still in effect.
+  pub fn tick_rate(&self) -> f32 {
+    let score = self
+      .snakes
+      .iter()
+      .map(|snake| snake.score)
+      .max()
+      .unwrap_or(0);
+    let mut rate = self.rules.speed.tick_rate(score);
+
+    if let Some(boost) = self.boost {
+      if self.tick < boost.until {
+        rate *= boost.factor;
+      }
+    }
+    if self
+      .snakes
+      .iter()
+      .any(|snake| snake.has_effect(PowerUpKind::Slow))
+    {
+      rate *= self.rules.power_ups.slow_factor;
+    }
+    rate
+  }
+
+  /// Advances the game by one tick, applying `turns` to player one's snake in
+  /// order. A turn that would reverse the way the snake travelled last tick
+  /// is ignored.
+  pub fn step(&mut self, turns: &[Direction]) -> Vec<Event> {
+    self.step_players(&[turns])
+  }
+
+  /// Advances the game by one tick with each player's turns, player one
+  /// first. All snakes move at once: heads meeting in a cell or crossing
+  /// each other crash both snakes, and a head running into any body crashes
+  /// that snake.
+  pub fn step_players(&mut self, turns: &[&[Direction]]) -> Vec<Event> {
+    let mut events = Vec::new();
+    if self.game_over() {
+      return events;
+    }
+    self.tick += 1;
+    self.elapsed += 1.0 / self.tick_rate();
+
+    for (player, turns) in turns.iter().enumerate() {
+      for turn in turns.iter() {
+        self.turn(player, *turn);
+      }
+    }
+
+    // Only a ghost's body can overlap itself, so only rules with ghosts need
+    // to check that a tail isn't leaving a cell the body still covers.
+    let ghosts = self.rules.power_ups.spawns(PowerUpKind::Ghost);
+    let mut moved = Vec::new();
+    for player in 0..self.snakes.len() {
+      if self.snakes[player].crash.is_some() {
+        continue;
+      }
+      let next = self.neighbour(self.snakes[player].head(), self.snakes[player].direction);
+      let snake = &mut self.snakes[player];
+      if !snake.update_position(next) {
+        continue;
+      }
+      if let Some(tail) = snake.vacated {
+        if !(ghosts && snake.body.contains(&tail)) {
+          self.occupancy.set(tail, Tile::Empty);
+        }
+      }
+      moved.push(player);
+    }
+
+    let tiles: Vec<Tile> = moved
+      .iter()
+      .map(|player| self.occupancy.get(self.snakes[*player].head()))
+      .collect();
+
+    let mut eaten = Vec::new();
+    let mut shielded = Vec::new();
+    let mut collected = Vec::new();
+    let mut caught = Vec::new();
+    for (i, player) in moved.iter().enumerate() {
+      let head = self.snakes[*player].head();
+      let previous = self.snakes[*player].previous_cell(0);
+      let head_on = moved.iter().filter(|other| *other != player).any(|other| {
+        let other = &self.snakes[*other];
+        other.head() == head || (other.head() == previous && other.previous_cell(0) == head)
+      });
+
+      let snake = &self.snakes[*player];
+      let crash = match tiles[i] {
+        _ if head_on => Some(Crash::HeadOn),
+        Tile::Wall if !self.board.contains(head) => Some(Crash::Border),
+        Tile::Wall | Tile::Portal => Some(Crash::Wall),
+        Tile::Snake if snake.bites_itself() && snake.has_effect(PowerUpKind::Ghost) => None,
+        Tile::Snake if snake.bites_itself() => Some(Crash::Itself),
+        Tile::Snake => Some(Crash::Snake),
+        Tile::Creature => match self.creature_at(head) {
+          Some(creature) if creature.is_deadly() => Some(Crash::Creature(creature.kind)),
+          _ => None,
+        },
+        Tile::Empty | Tile::Pellet | Tile::PowerUp => None,
+      };
+
+      match crash {
+        Some(crash)
+          if matches!(crash, Crash::Border | Crash::Wall)
+            && snake.has_effect(PowerUpKind::Shield) =>
+        {
+          shielded.push((*player, crash));
+        }
+        Some(crash) => {
+          self.crash(*player, crash, &mut events);
+          if let Crash::Creature(kind) = crash {
+            events.push(Event::CreatureHit(kind));
+          }
+        }
+        None if tiles[i] == Tile::Pellet => eaten.push(*player),
+        None if tiles[i] == Tile::PowerUp => collected.push(*player),
+        None if tiles[i] == Tile::Creature => caught.push(*player),
+        None => {}
+      }
+    }
+
+    for player in moved.iter() {
+      let snake = &self.snakes[*player];
+      if snake.crash.is_none() && !shielded.iter().any(|(shielded, _)| shielded == player) {
+        self.occupancy.set(snake.head(), Tile::Snake);
+      }
+    }
+
+    // A shield stops the snake where it was instead, unless another snake has
+    // just moved into the cell its tail left.
+    for (player, crash) in shielded {
+      let snake = &mut self.snakes[player];
+      snake.remove_effect(PowerUpKind::Shield);
+      events.push(Event::PowerUpExpired(PowerUpKind::Shield));
+
+      match snake.vacated {
+        Some(tail) if self.occupancy.get(tail) != Tile::Empty => {
+          self.crash(player, crash, &mut events);
+        }
+        tail => {
+          snake.undo_position();
+          if let Some(tail) = tail {
+            self.occupancy.set(tail, Tile::Snake);
+          }
+        }
+      }
+    }
+
+    for player in collected {
+      if let Some(power_up) = self.power_up.take() {
+        let duration = self.rules.power_ups.settings(power_up.kind).duration;
+        self.snakes[player].add_effect(power_up.kind, self.tick + duration as u64);
+        self.next_power_up = self.tick + self.rules.power_ups.interval as u64;
+        events.push(Event::PowerUpCollected(power_up.kind));
+      }
+    }
+
+    for player in caught {
+      let head = self.snakes[player].head();
+      self.creatures.retain(|creature| creature.cell != head);
+      let snake = &mut self.snakes[player];
+      snake.score = snake
+        .score
+        .saturating_add(self.rules.creatures.mouse_points);
+      snake.grow_body(self.rules.growth_per_pellet);
+      events.push(Event::MouseCaught);
+      self.spawn_creature(CreatureKind::Mouse);
+    }
+
+    for player in eaten {
+      let head = self.snakes[player].head();
+      let kind = match self.pellets.iter().position(|pellet| pellet.cell == head) {
+        Some(index) => self.pellets.remove(index).kind,
+        None => continue,
+      };
+      let settings = *self.rules.pellets.settings(kind);
+      let snake = &mut self.snakes[player];
+      snake.score = snake.score.saturating_add_signed(settings.points);
+      snake.eaten += 1;
+
+      match kind {
+        PelletKind::Poison | PelletKind::Shrink => {
+          for cell in snake.shrink(settings.segments) {
+            if !snake.body.contains(&cell) {
+              self.occupancy.set(cell, Tile::Empty);
+            }
+          }
+        }
+        _ => snake.grow_body(self.rules.growth_per_pellet),
+      }
+      if kind == PelletKind::Speed {
+        self.boost = Some(SpeedBoost {
+          factor: settings.speed,
+          until: self.tick + settings.duration as u64,
+        });
+      }
+      events.push(Event::Score(self.snakes[player].score));
+
+      if self.pellets_left() == Some(0) {
+        // Any other snake reaching a pellet this tick is too late.
+        self.won = true;
+        events.push(Event::BoardCleared);
+        break;
+      } else {
+        self.replace_pellet(&mut events);
+      }
+    }
+
+    if !self.won {
+      let tick = self.tick;
+      let (expired, kept) = self
+        .pellets
+        .drain(..)
+        .partition(|pellet| pellet.expires.is_some_and(|expires| tick >= expires));
+      self.pellets = kept;
+      for pellet in expired {
+        self.occupancy.set(pellet.cell, Tile::Empty);
+        self.replace_pellet(&mut events);
+      }
+
+      let due = self.respawns.iter().filter(|due| **due <= tick).count();
+      self.respawns.retain(|due| *due > tick);
+      for _ in 0..due {
+        self.respawn_pellet(&mut events);
+      }
+
+      self.pull_pellets();
+    }
+    if !self.game_over() {
+      self.move_creatures(&mut events);
+    }
+
+    for snake in self.snakes.iter_mut() {
+      for kind in snake.expire_effects(self.tick) {
+        events.push(Event::PowerUpExpired(kind));
+      }
+    }
+    self.update_power_up();
+
+    events
+  }
+
+  /// Stops `player`'s snake for good. The game is over once at most one
+  /// snake is left going, or straight away in a game for one.
+  fn crash(&mut self, player: usize, crash: Crash, events: &mut Vec<Event>) {
+    self.snakes[player].crash = Some(crash);
+    events.push(Event::SnakeCrashed);
+
+    let going = self
+      .snakes
+      .iter()
+      .filter(|snake| snake.crash.is_none())
+      .count();
+    self.crashed = going == 0 || (going == 1 && self.snakes.len() > 1);
+  }
+
+  /// The cell one step from `cell` in `direction`, wrapped around the board
+  /// if the rules allow it. Stepping into a portal comes out one step past
+  /// its other end, still going the same way.
+  pub fn neighbour(&self, cell: Cell, direction: Direction) -> Cell {
+    let next = self.step_from(cell, direction);
+    match self.portal_exit(next) {
+      Some(exit) => self.step_from(exit, direction),
+      None => next,
+    }
+  }
+
+  /// The other end of the portal on `cell`, if there is one.
+  pub fn portal_exit(&self, cell: Cell) -> Option<Cell> {
+    self.portals.iter().find_map(|(a, b)| {
+      if cell == *a {
+        Some(*b)
+      } else if cell == *b {
+        Some(*a)
+      } else {
+        None
+      }
+    })
+  }
+
+  fn step_from(&self, cell: Cell, direction: Direction) -> Cell {
+    let offset = direction.offset();
+    let next = (cell.0 + offset.0, cell.1 + offset.1);
+
+    match self.rules.borders {
+      Borders::Wrap => self.board.wrap(next),
+      Borders::Deadly => next,
+    }
+  }
+
+  /// Whether the snake's head could move into `cell` without dying.
+  pub fn is_safe(&self, cell: Cell) -> bool {
+    match self.occupancy.get(cell) {
+      Tile::Empty | Tile::Pellet | Tile::PowerUp => true,
+      Tile::Creature => self
+        .creature_at(cell)
+        .is_some_and(|creature| !creature.is_deadly()),
+      Tile::Wall | Tile::Snake | Tile::Portal => false,
+    }
+  }
+
+  fn turn(&mut self, player: usize, direction: Direction) {
+    if let Some(snake) = self.snakes.get_mut(player) {
+      if direction != Direction::None && direction != snake.travelled.opposite() {
+        snake.update_direction(direction);
+      }
+    }
+  }
+
+  /// Moves each creature whose turn it is this tick. A patrol or chaser
+  /// moving onto a snake's head crashes that snake.
+  fn move_creatures(&mut self, events: &mut Vec<Event>) {
+    let heads: Vec<(usize, Cell)> = self
+      .snakes
+      .iter()
+      .enumerate()
+      .filter(|(_, snake)| snake.crash.is_none())
+      .map(|(player, snake)| (player, snake.head()))
+      .collect();
+    let creatures = self.rules.creatures;
+
+    for index in 0..self.creatures.len() {
+      let creature = self.creatures[index];
+      self.creatures[index].previous = creature.cell;
+
+      let interval = match creature.kind {
+        CreatureKind::Patrol => creatures.patrol_interval,
+        CreatureKind::Mouse => creatures.mouse_interval,
+        CreatureKind::Chaser => creatures.chaser_interval,
+      };
+      if !self.tick.is_multiple_of(interval as u64) {
+        continue;
+      }
+
+      let next = match creature.kind {
+        CreatureKind::Patrol => self.patrol_move(index, &heads),
+        CreatureKind::Mouse => self.mouse_move(creature.cell, &heads),
+        CreatureKind::Chaser => self.chaser_move(creature.cell, &heads),
+      };
+      let next = match next {
+        Some(next) => next,
+        None => continue,
+      };
+
+      if let Some((player, _)) = heads.iter().find(|(_, head)| *head == next) {
+        self.crash(*player, Crash::Creature(creature.kind), events);
+        events.push(Event::CreatureHit(creature.kind));
+        continue;
+      }
+
+      self.occupancy.set(creature.cell, Tile::Empty);
+      self.occupancy.set(next, Tile::Creature);
+      self.creatures[index].cell = next;
+    }
+  }
+
+  /// Whether a creature could move onto `cell`. Only deadly ones move onto a
+  /// snake's head, and nothing moves onto anything else.
+  fn is_open(&self, cell: Cell, kind: CreatureKind, heads: &[(usize, Cell)]) -> bool {
+    match self.occupancy.get(cell) {
+      Tile::Empty => true,
+      Tile::Snake => kind != CreatureKind::Mouse && heads.iter().any(|(_, head)| *head == cell),
+      _ => false,
+    }
+  }
+
+  /// A patrol carries on the way it's going, or turns round if it can't.
+  fn patrol_move(&mut self, index: usize, heads: &[(usize, Cell)]) -> Option<Cell> {
+    let patrol = self.creatures[index];
+    for heading in [patrol.heading, patrol.heading.opposite()].iter() {
+      let offset = heading.offset();
+      let next = (patrol.cell.0 + offset.0, patrol.cell.1 + offset.1);
+      if self.is_open(next, CreatureKind::Patrol, heads) {
+        self.creatures[index].heading = *heading;
+        return Some(next);
+      }
+    }
+    None
+  }
+
+  /// A mouse runs from the nearest head once it's close, and otherwise
+  /// wanders about or sits still.
+  fn mouse_move(&mut self, cell: Cell, heads: &[(usize, Cell)]) -> Option<Cell> {
+    let moves: Vec<Cell> = DIRECTIONS
+      .iter()
+      .map(|direction| {
+        let offset = direction.offset();
+        (cell.0 + offset.0, cell.1 + offset.1)
+      })
+      .filter(|next| self.is_open(*next, CreatureKind::Mouse, heads))
+      .collect();
+    let nearest = |cell: Cell| heads.iter().map(|(_, head)| distance(cell, *head)).min();
+
+    match nearest(cell) {
+      Some(current) if current <= FLEE_DISTANCE => moves
+        .into_iter()
+        .filter(|next| nearest(*next) > Some(current))
+        .max_by_key(|next| nearest(*next)),
+      _ => {
+        let choice = self.rng.gen_range(0..moves.len() + 1);
+        moves.get(choice).copied()
+      }
+    }
+  }
+
+  /// A chaser steps towards the nearest head.
+  fn chaser_move(&self, cell: Cell, heads: &[(usize, Cell)]) -> Option<Cell> {
+    let target = heads
+      .iter()
+      .map(|(_, head)| *head)
+      .min_by_key(|head| distance(cell, *head))?;
+
+    DIRECTIONS
+      .iter()
+      .map(|direction| {
+        let offset = direction.offset();
+        (cell.0 + offset.0, cell.1 + offset.1)
+      })
+      .filter(|next| self.is_open(*next, CreatureKind::Chaser, heads))
+      .min_by_key(|next| distance(*next, target))
+  }
+
+  /// Puts a creature on a random empty cell well away from every snake's
+  /// head. Does nothing if there's no such cell.
+  fn spawn_creature(&mut self, kind: CreatureKind) {
+    let heads: Vec<Cell> = self.snakes.iter().map(|snake| snake.head()).collect();
+    let board = self.board;
+    let cells: Vec<Cell> = (0..board.height)
+      .flat_map(|y| (0..board.width).map(move |x| (x, y)))
+      .filter(|cell| self.occupancy.get(*cell) == Tile::Empty)
+      .filter(|cell| {
+        heads
+          .iter()
+          .all(|head| distance(*cell, *head) >= SPAWN_DISTANCE)
+      })
+      .collect();
+    if cells.is_empty() {
+      return;
+    }
+
+    let cell = cells[self.rng.gen_range(0..cells.len())];
+    self
+      .creatures
+      .push(Creature::new(kind, cell, Direction::None));
+    self.occupancy.set(cell, Tile::Creature);
+  }
+
+  /// Replaces a pellet that was eaten or disappeared, straight away or once
+  /// the rules' respawn delay has passed.
+  fn replace_pellet(&mut self, events: &mut Vec<Event>) {
+    match self.rules.respawn_delay {
+      0 => self.respawn_pellet(events),
+      delay => self.respawns.push(self.tick + delay as u64),
+    }
+  }
+
+  /// Puts a replacement pellet on the board if the rules still allow one. With
+  /// no free cell it tries again next tick, unless there's no pellet left
+  /// either, which means the snakes have filled the board.
+  fn respawn_pellet(&mut self, events: &mut Vec<Event>) {
+    if let Some(left) = self.pellets_left() {
+      if left as usize <= self.pellets.len() {
+        return;
+      }
+    }
+
+    if self.spawn_pellet() {
+      return;
+    }
+    if self.pellets.is_empty() {
+      self.won = true;
+      events.push(Event::BoardFull);
+    } else {
+      self.respawns.push(self.tick + 1);
+    }
+  }
+
+  /// Places a new pellet on a random free cell. Returns false if there is
+  /// nowhere left to put it.
+  fn spawn_pellet(&mut self) -> bool {
+    let free = self.occupancy.free();
+    if free == 0 {
+      return false;
+    }
+
+    let n = self.rng.gen_range(0..free);
+    match self.occupancy.nth_free(n) {
+      Some(cell) => {
+        let kind = self.pellet_kind();
+        let lifetime = self.rules.pellets.settings(kind).lifetime;
+        let mut pellet = Pellet::new(cell);
+        pellet.kind = kind;
+        pellet.expires = if lifetime > 0 {
+          Some(self.tick + lifetime as u64)
+        } else {
+          None
+        };
+        self.pellets.push(pellet);
+        self.occupancy.set(cell, Tile::Pellet);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Moves each pellet a cell closer to the first snake with a magnet that
+  /// it's in range of, if that cell is empty.
+  fn pull_pellets(&mut self) {
+    let range = self.rules.power_ups.magnet_range as i32;
+    let heads: Vec<Cell> = self
+      .snakes
+      .iter()
+      .filter(|snake| snake.crash.is_none() && snake.has_effect(PowerUpKind::Magnet))
+      .map(|snake| snake.head())
+      .collect();
+    if heads.is_empty() {
+      return;
+    }
+
+    for index in 0..self.pellets.len() {
+      let pellet = self.pellets[index].cell;
+      let head = match heads
+        .iter()
+        .find(|head| (head.0 - pellet.0).abs() + (head.1 - pellet.1).abs() <= range)
+      {
+        Some(head) => *head,
+        None => continue,
+      };
+
+      let (dx, dy) = (head.0 - pellet.0, head.1 - pellet.1);
+      let cell = if dx.abs() >= dy.abs() {
+        (pellet.0 + dx.signum(), pellet.1)
+      } else {
+        (pellet.0, pellet.1 + dy.signum())
+      };
+      if self.occupancy.get(cell) == Tile::Empty {
+        self.occupancy.set(pellet, Tile::Empty);
+        self.pellets[index].update_position(cell);
+        self.occupancy.set(cell, Tile::Pellet);
+      }
+    }
+  }
+
+  /// Takes away a power-up nobody picked up in time, and puts a new one on a
+  /// random free cell when it's due.
+  fn update_power_up(&mut self) {
+    let power_ups = self.rules.power_ups;
+    if let Some(power_up) = self.power_up {
+      if self.tick >= power_up.expires {
+        if self.occupancy.get(power_up.cell) == Tile::PowerUp {
+          self.occupancy.set(power_up.cell, Tile::Empty);
+        }
+        self.power_up = None;
+        self.next_power_up = self.tick + power_ups.interval as u64;
+      }
+      return;
+    }
+
+    let free = self.occupancy.free();
+    if power_ups.interval == 0 || self.tick < self.next_power_up || self.won || free == 0 {
+      return;
+    }
+
+    let n = self.rng.gen_range(0..free);
+    let weights: Vec<u32> = PowerUpKind::ALL
+      .iter()
+      .map(|kind| power_ups.settings(*kind).weight)
+      .collect();
+    // Capped like pellet weights in `pellet_kind`.
+    let total = power_ups.total_weight().unwrap_or(u32::MAX);
+    let mut pick = self.rng.gen_range(0..total);
+    let mut kind = PowerUpKind::Ghost;
+    for (candidate, weight) in PowerUpKind::ALL.iter().zip(weights) {
+      if pick < weight {
+        kind = *candidate;
+        break;
+      }
+      pick -= weight;
+    }
+
+    if let Some(cell) = self.occupancy.nth_free(n) {
+      self.occupancy.set(cell, Tile::PowerUp);
+      self.power_up = Some(PowerUp {
+        cell,
+        kind,
+        expires: self.tick + power_ups.lifetime as u64,
+      });
+    }
+  }
+
+  /// Picks the next pellet's kind by the rules' weights. Rules with only
+  /// normal pellets don't draw from `rng`, so their games play out as they
+  /// always have.
+  fn pellet_kind(&mut self) -> PelletKind {
+    let weights: Vec<u32> = PelletKind::ALL
+      .iter()
+      .map(|kind| self.rules.pellets.settings(*kind).weight)
+      .collect();
+    // Validated rules always fit. Weights that don't are capped rather than
+    // overflowing, which only skews the draw.
+    let total = self.rules.pellets.total_weight().unwrap_or(u32::MAX);
+    if total == weights[0] {
+      return PelletKind::Normal;
+    }
+
+    let mut n = self.rng.gen_range(0..total);
+    for (kind, weight) in PelletKind::ALL.iter().zip(weights) {
+      if n < weight {
+        return *kind;
+      }
+      n -= weight;
+    }
+    PelletKind::Normal
+  }
+}
+
+fn distance(a: Cell, b: Cell) -> i32 {
+  (a.0 - b.0).abs() + (a.1 - b.1).abs()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Rules where every kind of creature moves on every tick.
+  fn busy_creatures() -> Rules {
+    let mut rules = Rules::default();
+    rules.creatures.patrol_interval = 1;
+    rules.creatures.mouse_interval = 1;
+    rules.creatures.chaser_interval = 1;
+    rules
+  }
+
+  /// Puts a creature of `kind` on `cell`, heading `heading`.
+  fn put_creature(simulation: &mut Simulation, kind: CreatureKind, cell: Cell, heading: Direction) {
+    simulation
+      .creatures
+      .push(Creature::new(kind, cell, heading));
+    simulation.occupancy.set(cell, Tile::Creature);
+  }
+
+  /// Moves the first pellet onto `cell`.
+  fn put_pellet(simulation: &mut Simulation, cell: Cell) {
+    let old = simulation.pellets[0].cell;
+    simulation.occupancy.set(old, Tile::Empty);
+    simulation.pellets[0].update_position(cell);
+    simulation.occupancy.set(cell, Tile::Pellet);
+  }
+
+  #[test]
+  fn running_into_the_border_ends_the_game() {
+    let mut simulation = Simulation::new(Rules::default(), 0);
+    let start = simulation.snakes[0].head();
+    put_pellet(&mut simulation, (1, 1));
+
+    let mut events = simulation.step(&[Direction::Up]);
+    while !simulation.game_over() {
+      events = simulation.step(&[]);
+    }
+
+    assert!(simulation.crashed);
+    assert!(!simulation.won);
+    assert_eq!(simulation.snakes[0].crash, Some(Crash::Wall));
+    let top = simulation.board.height - 1;
+    assert_eq!(simulation.snakes[0].head(), (start.0, top));
+    assert_eq!(simulation.tick, (top - start.1) as u64);
+    assert!(events.iter().any(|e| matches!(e, Event::SnakeCrashed)));
+
+    let tick = simulation.tick;
+    assert!(simulation.step(&[Direction::Left]).is_empty());
+    assert_eq!(simulation.tick, tick);
+  }
+
+  #[test]
+  fn eating_a_pellet_scores_and_grows_the_snake() {
+    let mut simulation = Simulation::new(Rules::default(), 0);
+    let head = simulation.snakes[0].head();
+    put_pellet(&mut simulation, (head.0 + 1, head.1));
+
+    let events = simulation.step(&[Direction::Right]);
+    assert!(events.iter().any(|e| matches!(e, Event::Score(1))));
+    assert_eq!(simulation.snakes[0].score, 1);
+    assert_eq!(simulation.snakes[0].eaten, 1);
+    assert_eq!(simulation.snakes[0].body.len(), 1);
+    assert_eq!(simulation.pellets.len(), 1);
+    assert_ne!(simulation.pellets[0].cell, simulation.snakes[0].head());
+
+    simulation.step(&[]);
+    assert_eq!(simulation.snakes[0].body.len(), 2);
+    assert_eq!(simulation.occupancy.get(head), Tile::Empty);
+    assert_eq!(simulation.occupancy.get((head.0 + 1, head.1)), Tile::Snake);
+    assert_eq!(simulation.occupancy.get((head.0 + 2, head.1)), Tile::Snake);
+  }
+
+  #[test]
+  fn scores_stay_between_zero_and_the_largest_score() {
+    for (points, start, end) in [(-5, 3, 0), (i32::MAX, u32::MAX - 1, u32::MAX)].iter() {
+      let mut rules = Rules::default();
+      rules.pellets.normal.points = *points;
+      let mut simulation = Simulation::new(rules, 0);
+      simulation.snakes[0].score = *start;
+      let head = simulation.snakes[0].head();
+      put_pellet(&mut simulation, (head.0 + 1, head.1));
+
+      simulation.step(&[Direction::Right]);
+      assert_eq!(simulation.snakes[0].score, *end);
+    }
+  }
+
+  #[test]
+  fn growth_stops_adding_up_at_its_limit() {
+    let rules = Rules {
+      growth_per_pellet: 4_000_000_000,
+      ..Default::default()
+    };
+    let mut simulation = Simulation::new(rules, 0);
+    let head = simulation.snakes[0].head();
+
+    for step in 1..=2 {
+      put_pellet(&mut simulation, (head.0 + step, head.1));
+      simulation.step(&[Direction::Right]);
+    }
+
+    assert_eq!(simulation.snakes[0].eaten, 2);
+    simulation.step(&[]);
+    assert_eq!(simulation.snakes[0].body.len(), 3);
+  }
+
+  #[test]
+  fn filling_the_board_wins() {
+    let rules = Rules {
+      board_width: 3,
+      board_height: 1,
+      borders: Borders::Wrap,
+      ..Default::default()
+    };
+    let mut simulation = Simulation::new(rules, 0);
+
+    let mut events = simulation.step(&[Direction::Right]);
+    while !simulation.game_over() && simulation.tick < 20 {
+      events = simulation.step(&[]);
+    }
+
+    assert!(simulation.won);
+    assert!(!simulation.crashed);
+    assert_eq!(simulation.snakes[0].body.len(), 3);
+    assert_eq!(simulation.occupancy.free(), 0);
+    assert!(events.iter().any(|e| matches!(e, Event::BoardFull)));
+  }
+
+  #[test]
+  fn the_board_is_cleared_once_when_two_snakes_eat_the_last_pellets() {
+    let rules = Rules {
+      pellet_count: 2,
+      total_pellets: 3,
+      ..Default::default()
+    };
+    let level = Level::from_rules(&rules);
+    let mut simulation = Simulation::with_players(rules, level, 2, 0);
+    // Two pellets have already gone, so only one of the two on the board is
+    // left to eat.
+    simulation.snakes[0].eaten = 2;
+
+    let turns: Vec<Direction> = simulation
+      .snakes
+      .iter()
+      .map(|snake| match snake.direction {
+        Direction::None => Direction::Up,
+        direction => direction,
+      })
+      .collect();
+    for (index, turn) in turns.iter().enumerate() {
+      let head = simulation.snakes[index].head();
+      let offset = turn.offset();
+      let old = simulation.pellets[index].cell;
+      simulation.occupancy.set(old, Tile::Empty);
+      let cell = (head.0 + offset.0, head.1 + offset.1);
+      simulation.pellets[index].update_position(cell);
+      simulation.occupancy.set(cell, Tile::Pellet);
+    }
+
+    let step: Vec<&[Direction]> = turns.iter().map(std::slice::from_ref).collect();
+    let events = simulation.step_players(&step);
+    let cleared = events
+      .iter()
+      .filter(|e| matches!(e, Event::BoardCleared))
+      .count();
+    assert_eq!(cleared, 1);
+    assert!(simulation.won);
+    assert_eq!(simulation.pellets_left(), Some(0));
+  }
+
+  #[test]
+  fn a_patrol_turns_round_at_a_wall() {
+    let mut simulation = Simulation::new(busy_creatures(), 0);
+    put_pellet(&mut simulation, (40, 40));
+    put_creature(
+      &mut simulation,
+      CreatureKind::Patrol,
+      (2, 10),
+      Direction::Left,
+    );
+
+    simulation.step(&[]);
+    assert_eq!(simulation.creatures[0].cell, (1, 10));
+    simulation.step(&[]);
+    assert_eq!(simulation.creatures[0].cell, (2, 10));
+    assert_eq!(simulation.creatures[0].heading, Direction::Right);
+    assert_eq!(simulation.occupancy.get((1, 10)), Tile::Empty);
+    assert_eq!(simulation.occupancy.get((2, 10)), Tile::Creature);
+  }
+
+  #[test]
+  fn a_mouse_runs_from_a_head_that_comes_close() {
+    let mut simulation = Simulation::new(busy_creatures(), 0);
+    let head = simulation.snakes[0].head();
+    put_pellet(&mut simulation, (40, 40));
+    put_creature(
+      &mut simulation,
+      CreatureKind::Mouse,
+      (head.0 + 3, head.1),
+      Direction::None,
+    );
+
+    simulation.step(&[]);
+    assert_eq!(distance(simulation.creatures[0].cell, head), 4);
+  }
+
+  #[test]
+  fn catching_a_mouse_scores_and_brings_another() {
+    let mut rules = busy_creatures();
+    rules.creatures.mouse_points = u32::MAX;
+    let mut simulation = Simulation::new(rules, 0);
+    simulation.snakes[0].score = 1;
+    let head = simulation.snakes[0].head();
+    put_pellet(&mut simulation, (40, 40));
+    put_creature(
+      &mut simulation,
+      CreatureKind::Mouse,
+      (head.0 + 1, head.1),
+      Direction::None,
+    );
+
+    let events = simulation.step(&[Direction::Right]);
+    assert!(events.iter().any(|e| matches!(e, Event::MouseCaught)));
+    assert!(!simulation.game_over());
+    assert_eq!(simulation.snakes[0].score, u32::MAX);
+    assert_eq!(simulation.creatures.len(), 1);
+    assert!(
+      distance(simulation.creatures[0].cell, simulation.snakes[0].head()) >= SPAWN_DISTANCE - 1
+    );
+  }
+
+  #[test]
+  fn a_chaser_catches_a_head_that_stays_put() {
+    let mut simulation = Simulation::new(busy_creatures(), 0);
+    let head = simulation.snakes[0].head();
+    put_pellet(&mut simulation, (40, 40));
+    put_creature(
+      &mut simulation,
+      CreatureKind::Chaser,
+      (head.0 + 2, head.1),
+      Direction::None,
+    );
+
+    simulation.step(&[]);
+    assert_eq!(simulation.creatures[0].cell, (head.0 + 1, head.1));
+    assert!(!simulation.game_over());
+
+    let events = simulation.step(&[]);
+    assert!(simulation.game_over());
+    assert_eq!(
+      simulation.snakes[0].crash,
+      Some(Crash::Creature(CreatureKind::Chaser))
+    );
+    assert!(events
+      .iter()
+      .any(|e| matches!(e, Event::CreatureHit(CreatureKind::Chaser))));
+  }
+
+  /// Rules with only `kind` of power-up turning up, though not for a long
+  /// while.
+  fn with_power_up(kind: PowerUpKind) -> Rules {
+    let mut rules = Rules {
+      start_length: 5,
+      ..Default::default()
+    };
+    rules.power_ups.interval = 1000;
+    rules.power_ups.ghost.weight = 0;
+    rules.power_ups.slow.weight = 0;
+    rules.power_ups.magnet.weight = 0;
+    rules.power_ups.shield.weight = 0;
+    match kind {
+      PowerUpKind::Ghost => rules.power_ups.ghost.weight = 1,
+      PowerUpKind::Slow => rules.power_ups.slow.weight = 1,
+      PowerUpKind::Magnet => rules.power_ups.magnet.weight = 1,
+      PowerUpKind::Shield => rules.power_ups.shield.weight = 1,
+    }
+    rules
+  }
+
+  /// Runs the snake round a tight loop so its head comes down onto its own
+  /// body, and returns the cell it comes down on.
+  fn loop_back(simulation: &mut Simulation) -> Cell {
+    for _ in 0..5 {
+      simulation.step(&[Direction::Right]);
+    }
+    let head = simulation.snakes[0].head();
+    for turn in [Direction::Up, Direction::Left, Direction::Down].iter() {
+      simulation.step(&[*turn]);
+    }
+    (head.0 - 1, head.1)
+  }
+
+  #[test]
+  fn a_ghost_passes_through_its_own_body() {
+    let mut simulation = Simulation::new(with_power_up(PowerUpKind::Ghost), 0);
+    put_pellet(&mut simulation, (40, 40));
+    simulation.snakes[0].add_effect(PowerUpKind::Ghost, 100);
+
+    let overlap = loop_back(&mut simulation);
+    assert!(!simulation.game_over());
+    let snake = &simulation.snakes[0];
+    assert_eq!(snake.head(), overlap);
+    assert_eq!(snake.body.back(), Some(&overlap));
+
+    // The tail leaves the cell the head still covers.
+    simulation.step(&[]);
+    assert_eq!(simulation.occupancy.get(overlap), Tile::Snake);
+    assert!(simulation.snakes[0].body.contains(&overlap));
+  }
+
+  #[test]
+  fn without_a_ghost_the_snake_bites_itself() {
+    let mut simulation = Simulation::new(with_power_up(PowerUpKind::Ghost), 0);
+    put_pellet(&mut simulation, (40, 40));
+
+    loop_back(&mut simulation);
+    assert_eq!(simulation.snakes[0].crash, Some(Crash::Itself));
+  }
+
+  #[test]
+  fn a_shield_stops_the_snake_at_a_wall_once() {
+    let mut simulation = Simulation::new(with_power_up(PowerUpKind::Shield), 0);
+    put_pellet(&mut simulation, (40, 40));
+    simulation.snakes[0].add_effect(PowerUpKind::Shield, 1000);
+
+    while simulation.snakes[0].head().0 > 1 {
+      simulation.step(&[Direction::Left]);
+    }
+    let body = simulation.snakes[0].body.clone();
+    let events = simulation.step(&[]);
+    assert!(!simulation.game_over());
+    assert!(events
+      .iter()
+      .any(|e| matches!(e, Event::PowerUpExpired(PowerUpKind::Shield))));
+
+    let snake = &simulation.snakes[0];
+    assert_eq!(snake.body, body);
+    assert_eq!(snake.direction, Direction::None);
+    assert!(!snake.has_effect(PowerUpKind::Shield));
+    assert!(body
+      .iter()
+      .all(|cell| simulation.occupancy.get(*cell) == Tile::Snake));
+
+    simulation.step(&[Direction::Left]);
+    assert_eq!(simulation.snakes[0].crash, Some(Crash::Wall));
+  }
+
+  #[test]
+  fn a_magnet_pulls_pellets_in_range() {
+    let mut simulation = Simulation::new(with_power_up(PowerUpKind::Magnet), 0);
+    let head = simulation.snakes[0].head();
+    let range = simulation.rules.power_ups.magnet_range as i32;
+    put_pellet(&mut simulation, (head.0 + range + 1, head.1));
+
+    simulation.step(&[]);
+    assert_eq!(simulation.pellets[0].cell, (head.0 + range + 1, head.1));
+
+    simulation.snakes[0].add_effect(PowerUpKind::Magnet, 1000);
+    simulation.step(&[]);
+    assert_eq!(simulation.pellets[0].cell, (head.0 + range + 1, head.1));
+    put_pellet(&mut simulation, (head.0 + range, head.1));
+    simulation.step(&[]);
+    assert_eq!(simulation.pellets[0].cell, (head.0 + range - 1, head.1));
+    assert_eq!(
+      simulation.occupancy.get((head.0 + range, head.1)),
+      Tile::Empty
+    );
+    assert_eq!(
+      simulation.occupancy.get((head.0 + range - 1, head.1)),
+      Tile::Pellet
+    );
+  }
+
+  #[test]
+  fn effects_wear_off_on_time() {
+    let mut simulation = Simulation::new(with_power_up(PowerUpKind::Slow), 0);
+    put_pellet(&mut simulation, (40, 40));
+    let rate = simulation.tick_rate();
+    simulation.snakes[0].add_effect(PowerUpKind::Slow, 2);
+    assert!(simulation.tick_rate() < rate);
+
+    let events = simulation.step(&[]);
+    assert!(events.is_empty());
+    let events = simulation.step(&[]);
+    assert!(events
+      .iter()
+      .any(|e| matches!(e, Event::PowerUpExpired(PowerUpKind::Slow))));
+    assert!(!simulation.snakes[0].has_effect(PowerUpKind::Slow));
+    assert_eq!(simulation.tick_rate(), rate);
+  }
+
+  #[test]
+  fn a_power_up_nobody_picks_up_goes_away() {
+    let mut rules = with_power_up(PowerUpKind::Ghost);
+    rules.power_ups.interval = 2;
+    rules.power_ups.lifetime = 3;
+    let mut simulation = Simulation::new(rules, 0);
+    put_pellet(&mut simulation, (40, 40));
+
+    while simulation.power_up.is_none() {
+      simulation.step(&[]);
+    }
+    let power_up = simulation.power_up.unwrap();
+    assert_eq!(power_up.kind, PowerUpKind::Ghost);
+    assert_eq!(simulation.occupancy.get(power_up.cell), Tile::PowerUp);
+
+    while simulation.tick < power_up.expires {
+      simulation.step(&[]);
+    }
+    assert_eq!(simulation.power_up, None);
+    assert_eq!(simulation.occupancy.get(power_up.cell), Tile::Empty);
+  }
+
+  /// Steps a game for several players, turning `player` to `turn` and
+  /// leaving the others as they are.
+  fn steer(simulation: &mut Simulation, player: usize, turn: Direction) -> Vec<Event> {
+    let mut turns: Vec<&[Direction]> = vec![&[]; simulation.snakes.len()];
+    let turn = [turn];
+    turns[player] = &turn;
+    simulation.step_players(&turns)
+  }
+
+  #[test]
+  fn the_rest_play_on_until_one_snake_is_left() {
+    let rules = Rules::default();
+    let level = Level::from_rules(&rules);
+    let mut simulation = Simulation::with_players(rules, level, 3, 0);
+    put_pellet(&mut simulation, (40, 40));
+
+    while simulation.snakes[2].crash.is_none() {
+      steer(&mut simulation, 2, Direction::Right);
+    }
+    assert!(!simulation.game_over());
+    assert_eq!(simulation.winner(), None);
+
+    let stopped = simulation.snakes[2].body.clone();
+    while !simulation.game_over() {
+      steer(&mut simulation, 1, Direction::Down);
+    }
+    assert_eq!(simulation.snakes[2].body, stopped);
+    assert_eq!(simulation.snakes[0].crash, None);
+    assert!(simulation.snakes[1].crash.is_some());
+    assert_eq!(simulation.winner(), Some(0));
+  }
+
+  /// A game on `text`, a level with a corridor running right from the spawn
+  /// point, with the pellet moved out of the way to `pellet`.
+  fn corridor(text: &str, pellet: Cell) -> Simulation {
+    let rules = Rules {
+      start_length: 3,
+      ..Default::default()
+    };
+    let level = Level::parse("", text).unwrap();
+    let mut simulation = Simulation::with_level(rules, level, 0);
+    put_pellet(&mut simulation, pellet);
+    simulation
+  }
+
+  #[test]
+  fn a_portal_leads_one_step_past_its_other_end() {
+    let simulation = corridor("##########\n#S.1..1..#\n##########\n", (4, 1));
+
+    assert_eq!(simulation.portal_exit((3, 1)), Some((6, 1)));
+    assert_eq!(simulation.portal_exit((6, 1)), Some((3, 1)));
+    assert_eq!(simulation.portal_exit((2, 1)), None);
+    assert_eq!(simulation.neighbour((2, 1), Direction::Right), (7, 1));
+    assert_eq!(simulation.neighbour((7, 1), Direction::Left), (2, 1));
+    assert_eq!(simulation.neighbour((1, 1), Direction::Right), (2, 1));
+  }
+
+  #[test]
+  fn a_body_split_across_a_portal_follows_the_head() {
+    let mut simulation = corridor("##########\n#S.1..1..#\n##########\n", (4, 1));
+
+    simulation.step(&[Direction::Right]);
+    simulation.step(&[]);
+    let snake = &simulation.snakes[0];
+    assert_eq!(snake.body, vec![(7, 1), (2, 1), (1, 1)]);
+    for (cell, tile) in [
+      ((1, 1), Tile::Snake),
+      ((2, 1), Tile::Snake),
+      ((3, 1), Tile::Portal),
+      ((6, 1), Tile::Portal),
+      ((7, 1), Tile::Snake),
+    ]
+    .iter()
+    {
+      assert_eq!(simulation.occupancy.get(*cell), *tile, "{:?}", cell);
+    }
+
+    simulation.step(&[]);
+    assert!(!simulation.game_over());
+    assert_eq!(simulation.snakes[0].body, vec![(8, 1), (7, 1), (2, 1)]);
+    assert_eq!(simulation.occupancy.get((1, 1)), Tile::Empty);
+  }
+
+  #[test]
+  fn a_portal_out_into_a_wall_is_deadly() {
+    let mut simulation = corridor("#######\n#S.1.1#\n#######\n", (4, 1));
+
+    simulation.step(&[Direction::Right]);
+    assert!(!simulation.game_over());
+    simulation.step(&[]);
+    assert_eq!(simulation.snakes[0].crash, Some(Crash::Wall));
+    assert_eq!(simulation.snakes[0].head(), (6, 1));
+  }
+
+  /// A versus game on a square board `size` cells across.
+  fn versus(size: i32) -> Simulation {
+    let rules = Rules {
+      board_width: size,
+      board_height: size,
+      start_length: 3,
+      ..Default::default()
+    };
+    let level = Level::from_rules(&rules);
+    let mut simulation = Simulation::with_players(rules, level, 2, 0);
+    put_pellet(&mut simulation, (1, 1));
+    simulation
+  }
+
+  #[test]
+  fn heads_meeting_in_a_cell_crash_both_snakes() {
+    let mut simulation = versus(12);
+    assert_eq!(simulation.snakes[0].head(), (4, 6));
+    assert_eq!(simulation.snakes[1].head(), (8, 6));
+
+    simulation.step_players(&[&[Direction::Right], &[Direction::Left]]);
+    assert!(!simulation.game_over());
+    simulation.step_players(&[&[], &[]]);
+
+    assert!(simulation.game_over());
+    for snake in simulation.snakes.iter() {
+      assert_eq!(snake.head(), (6, 6));
+      assert_eq!(snake.crash, Some(Crash::HeadOn));
+    }
+    assert_eq!(simulation.winner(), None);
+  }
+
+  #[test]
+  fn heads_crossing_crash_both_snakes() {
+    let mut simulation = versus(9);
+    assert_eq!(simulation.snakes[0].head(), (3, 4));
+    assert_eq!(simulation.snakes[1].head(), (6, 4));
+
+    simulation.step_players(&[&[Direction::Right], &[Direction::Left]]);
+    assert!(!simulation.game_over());
+    simulation.step_players(&[&[], &[]]);
+
+    assert!(simulation.game_over());
+    assert_eq!(simulation.snakes[0].head(), (5, 4));
+    assert_eq!(simulation.snakes[1].head(), (4, 4));
+    for snake in simulation.snakes.iter() {
+      assert_eq!(snake.crash, Some(Crash::HeadOn));
+    }
+    assert_eq!(simulation.winner(), None);
+  }
+
+  #[test]
+  fn a_head_running_into_another_body_loses() {
+    let mut simulation = versus(12);
+    let turns = [
+      [Direction::Right, Direction::Down],
+      [Direction::Right, Direction::Left],
+      [Direction::Up, Direction::Left],
+    ];
+    for [first, second] in turns.iter() {
+      simulation.step_players(&[&[*first], &[*second]]);
+      assert!(!simulation.game_over());
+    }
+    assert_eq!(simulation.snakes[1].head(), (6, 5));
+
+    simulation.step_players(&[&[], &[Direction::Up]]);
+    assert!(simulation.game_over());
+    assert_eq!(simulation.snakes[0].crash, None);
+    assert_eq!(simulation.snakes[1].crash, Some(Crash::Snake));
+    assert_eq!(simulation.winner(), Some(0));
+  }
+}
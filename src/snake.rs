@@ -1,117 +1,188 @@
-use crate::coords;
-use crate::util;
+use crate::board::Cell;
+use crate::creature::CreatureKind;
+use crate::power_up::{Effect, PowerUpKind};
 use crate::util::Direction;
-use crate::util::Direction::*;
-use cgmath::Vector2;
-use dynamo_lib::geometry::quad::Quad;
+use std::collections::VecDeque;
+
+/// What a snake ran into.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Crash {
+  /// The edge of the board, when the borders are deadly.
+  Border,
+  Wall,
+  /// Its own body.
+  Itself,
+  /// Another snake's body.
+  Snake,
+  /// Another snake's head, meeting it or crossing it.
+  HeadOn,
+  /// A creature, by running into it or being caught by it.
+  Creature(CreatureKind),
+}
 
 pub struct Snake {
-  pub body: Vec<Quad>,
-  pub segment_size: Vector2<f32>,
-  pub position: Vector2<f32>,
+  /// Occupied cells, head first.
+  pub body: VecDeque<Cell>,
+  /// The tail cell given up on the last tick, if the snake moved without
+  /// growing.
+  pub vacated: Option<Cell>,
+  /// Whether the snake moved on the last tick.
+  moved: bool,
   pub direction: Direction,
-  pub speed: f32,
+  /// The direction the snake moved in on the last tick it moved.
+  pub travelled: Direction,
   pub score: u32,
-  pub visible: bool,
+  /// Pellets eaten this game.
+  pub eaten: u32,
+  /// What the snake ran into, once it has crashed and stopped.
+  pub crash: Option<Crash>,
+  /// Power-ups that haven't worn off yet.
+  pub effects: Vec<Effect>,
+  start: Cell,
+  start_length: u32,
+  growth: u32,
 }
 
 impl Snake {
-  pub fn new(position: Vector2<f32>, size: Vector2<f32>) -> Snake {
+  /// A snake of one segment at `start` that grows to `start_length` as it
+  /// first moves off.
+  pub fn new(start: Cell, start_length: u32) -> Snake {
     Snake {
-      body: vec![Quad::new(position, size)],
-      segment_size: size,
-      position: position,
-      direction: None,
-      speed: util::STARTING_SNAKE_SPEED,
+      body: vec![start].into(),
+      vacated: None,
+      moved: false,
+      direction: Direction::None,
+      travelled: Direction::None,
       score: 0,
-      visible: false,
+      eaten: 0,
+      crash: None,
+      effects: Vec::new(),
+      start,
+      start_length,
+      growth: start_length - 1,
     }
   }
 
   pub fn reset(&mut self) {
     self.score = 0;
-    self.speed = util::STARTING_SNAKE_SPEED;
-    self.update_position((0.0, 0.0).into());
-    self.update_direction(None);
-    self.reset_body();
+    self.eaten = 0;
+    self.crash = None;
+    self.effects.clear();
+    self.growth = self.start_length - 1;
+    self.update_direction(Direction::None);
+    self.travelled = Direction::None;
+    self.body = vec![self.start].into();
+    self.vacated = None;
+    self.moved = false;
   }
 
-  pub fn reset_body(&mut self) {
-    self.body = vec![self.head()];
-  }
-
-  pub fn grow_body(&mut self) {
-    let mut last_segment = self.body[self.body.len() - 1].clone();
-    last_segment.position = (
-      last_segment.position.x + -1.0 * self.direction().x * self.segment_size.x,
-      last_segment.position.y + -1.0 * self.direction().y * self.segment_size.y,
-    )
-      .into();
-
-    self.body.append(&mut vec![last_segment]);
+  pub fn grow_body(&mut self, segments: u32) {
+    self.growth = self.growth.saturating_add(segments);
   }
 
   pub fn update_direction(&mut self, direction: Direction) {
     self.direction = direction;
   }
 
-  pub fn direction(&self) -> Vector2<f32> {
-    match self.direction {
-      None => (0.0, 0.0).into(),
-      Up => (0.0, 1.0).into(),
-      Down => (0.0, -1.0).into(),
-      Left => (-1.0, 0.0).into(),
-      Right => (1.0, 0.0).into(),
-    }
+  pub fn head(&self) -> Cell {
+    self.body[0]
   }
 
-  pub fn position(&self) -> Vector2<f32> {
-    self.position
+  /// Where segment `index` was before the last tick, for interpolating
+  /// between ticks.
+  pub fn previous_cell(&self, index: usize) -> Cell {
+    if !self.moved {
+      self.body[index]
+    } else if index + 1 < self.body.len() {
+      self.body[index + 1]
+    } else {
+      self.vacated.unwrap_or(self.body[index])
+    }
   }
 
-  pub fn update_position(&mut self, position: Vector2<f32>) {
-    self.position = position;
+  /// Moves the head on to `next`, the cell one step in the current direction
+  /// once wrapping and portals are taken into account. That cell needn't be
+  /// next to the old head, so the body isn't always in one piece. Returns
+  /// whether the snake moved.
+  pub fn update_position(&mut self, next: Cell) -> bool {
+    self.vacated = None;
+    self.moved = self.direction != Direction::None;
+    if !self.moved {
+      return false;
+    }
+
+    self.travelled = self.direction;
+    self.body.push_front(next);
 
-    let head = self.head();
-    let cur_snake_coords = coords::snake_coordinates(self.segment_size, head.position);
-    let new_snake_coords = coords::snake_coordinates(self.segment_size, position);
+    if self.growth > 0 {
+      self.growth -= 1;
+    } else {
+      self.vacated = self.body.pop_back();
+    }
 
-    if cur_snake_coords.0 != new_snake_coords.0 || cur_snake_coords.1 != new_snake_coords.1 {
-      let new_screen_coords = coords::screen_coordinates(self.segment_size, new_snake_coords);
+    true
+  }
 
-      let new_head = vec![Quad::new(new_screen_coords.into(), head.size)];
-      let old_body = &self.body[0..self.body.len() - 1];
+  /// Moves the head back to where it was before the last tick and stops the
+  /// snake there. The tail comes back too if it moved.
+  pub fn undo_position(&mut self) {
+    if !self.moved {
+      return;
+    }
 
-      self.body = [&new_head[..], old_body].concat();
+    self.body.pop_front();
+    match self.vacated.take() {
+      Some(tail) => self.body.push_back(tail),
+      None => self.growth = self.growth.saturating_add(1),
     }
+    self.moved = false;
+    self.update_direction(Direction::None);
   }
 
-  fn head(&self) -> Quad {
-    self.body[0]
+  pub fn has_effect(&self, kind: PowerUpKind) -> bool {
+    self.effects.iter().any(|effect| effect.kind == kind)
   }
 
-  pub fn intersects(&self, quad: &Quad) -> bool {
-    let snake_coords = coords::snake_coordinates(self.segment_size, self.position);
-    let screen_coords = coords::screen_coordinates(self.segment_size, snake_coords);
-
-    let radii = self.segment_size * 0.5;
-    let min = screen_coords - radii;
-    let max = screen_coords + radii;
+  /// Gives the snake `kind` until tick `until`, replacing any it already had.
+  pub fn add_effect(&mut self, kind: PowerUpKind, until: u64) {
+    self.remove_effect(kind);
+    self.effects.push(Effect { kind, until });
+  }
 
-    let b_radii = Vector2 {
-      x: quad.size.x,
-      y: quad.size.y,
-    };
-    let b_min = quad.position - b_radii;
-    let b_max = quad.position + b_radii;
+  pub fn remove_effect(&mut self, kind: PowerUpKind) {
+    self.effects.retain(|effect| effect.kind != kind);
+  }
 
-    min.x < b_max.x && max.x > b_min.x && min.y < b_max.y && max.y > b_min.y
+  /// Takes off the effects that wear off by `tick` and returns them.
+  pub fn expire_effects(&mut self, tick: u64) -> Vec<PowerUpKind> {
+    let expired = self
+      .effects
+      .iter()
+      .filter(|effect| effect.until <= tick)
+      .map(|effect| effect.kind)
+      .collect();
+    self.effects.retain(|effect| effect.until > tick);
+    expired
   }
 
-  pub fn collides(&self, quad: &Quad) -> bool {
-    let snake_coords = coords::snake_coordinates(self.segment_size, self.position);
-    let quad_coords = coords::snake_coordinates(quad.size, quad.position);
+  /// Takes up to `segments` off the tail, cancelling growth still to come
+  /// first. The head always stays. Returns the cells given up.
+  pub fn shrink(&mut self, segments: u32) -> Vec<Cell> {
+    let cancelled = segments.min(self.growth);
+    self.growth -= cancelled;
+
+    let mut removed = Vec::new();
+    for _ in cancelled..segments {
+      if self.body.len() <= 1 {
+        break;
+      }
+      removed.extend(self.body.pop_back());
+    }
+    removed
+  }
 
-    snake_coords.0 == quad_coords.0 && snake_coords.1 == quad_coords.1
+  /// Whether the head is on one of the snake's other segments.
+  pub fn bites_itself(&self) -> bool {
+    self.body.iter().skip(1).any(|cell| *cell == self.head())
   }
 }
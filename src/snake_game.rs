@@ -4,6 +4,11 @@ use dynamo_lib::renderer::render_text::TextRenderer;
 use dynamo_lib::sound::SoundSystem;
 use dynamo_lib::Game;
 
+use snake::bindings::Bindings;
+use snake::event::Event;
+use snake::net::Client;
+use snake::options::Options;
+use snake::replay::Replay;
 use std::io::Cursor;
 
 use crate::input::Input;
@@ -28,14 +33,6 @@ impl SoundPack {
   }
 }
 
-#[derive(Debug, Copy, Clone)]
-pub enum Event {
-  ButtonPressed,
-  FocusChanged,
-  SnakeCrashed,
-  Score(u32),
-}
-
 pub struct SnakeGame {
   pub input: Input,
   events: Vec<Event>,
@@ -45,20 +42,33 @@ pub struct SnakeGame {
   play_system: PlaySystem,
   pause_system: PauseSystem,
   game_over_system: GameOverSystem,
+  high_score_system: HighScoreSystem,
+  controls_system: ControlsSystem,
   sound_pack: SoundPack,
 }
 
 impl SnakeGame {
-  pub fn new() -> Self {
+  pub fn new(options: Options, playback: Option<Replay>, client: Option<Client>) -> Self {
+    let state = State::new(options, playback, client);
+    let bindings = match &state.bindings_path {
+      Some(path) => Bindings::load(path).unwrap_or_else(|error| {
+        eprintln!("Couldn't load controls from {}: {}", path.display(), error);
+        Bindings::default()
+      }),
+      None => Bindings::default(),
+    };
+
     Self {
-      input: Input::new(),
+      input: Input::new(bindings),
       events: Vec::new(),
-      state: State::new(),
+      state,
       menu_system: MenuSystem,
       visibility_system: VisibilitySystem,
       play_system: PlaySystem,
       pause_system: PauseSystem,
-      game_over_system: GameOverSystem::new(),
+      game_over_system: GameOverSystem,
+      high_score_system: HighScoreSystem,
+      controls_system: ControlsSystem,
       sound_pack: SoundPack::new(),
     }
   }
@@ -70,10 +80,10 @@ impl Game for SnakeGame {
     geometry: &mut Geometry,
     text_renderer: &mut TextRenderer,
     _sound_system: &SoundSystem,
-    _window_size: (f32, f32),
+    window_size: (f32, f32),
   ) {
     self.menu_system.start(&mut self.state);
-    self.state.initialize(geometry, text_renderer);
+    self.state.initialize(geometry, text_renderer, window_size);
   }
 
   fn update(
@@ -84,10 +94,18 @@ impl Game for SnakeGame {
   ) {
     for event in &self.events {
       match event {
-        Event::FocusChanged | Event::ButtonPressed | Event::SnakeCrashed => {
+        Event::FocusChanged
+        | Event::ButtonPressed
+        | Event::SnakeCrashed
+        | Event::CreatureHit(_) => {
           sound_system.queue(self.sound_pack.bounce());
         }
-        Event::Score(_) => {
+        Event::Score(_)
+        | Event::BoardFull
+        | Event::BoardCleared
+        | Event::PowerUpCollected(_)
+        | Event::PowerUpExpired(_)
+        | Event::MouseCaught => {
           sound_system.queue(self.sound_pack.bounce());
         }
       }
@@ -104,7 +122,15 @@ impl Game for SnakeGame {
           .menu_system
           .update_state(&mut self.input, &mut self.state, &mut self.events);
         if self.state.game_state == GameState::Playing {
+          self.input.clear();
           self.play_system.start(&mut self.state);
+        } else if self.state.game_state == GameState::HighScores {
+          self.high_score_system.start(&mut self.state);
+        } else if self.state.game_state == GameState::Controls {
+          self.controls_system.start(&mut self.state);
+        } else if self.state.game_state == GameState::Paused {
+          self.input.clear();
+          self.pause_system.start(&mut self.state);
         }
       }
       GameState::Playing => {
@@ -115,13 +141,21 @@ impl Game for SnakeGame {
         if self.state.game_state == GameState::MainMenu {
           self.menu_system.start(&mut self.state);
         } else if self.state.game_state == GameState::GameOver {
+          self.input.clear();
           self.game_over_system.start(&mut self.state);
+        } else if self.state.game_state == GameState::Paused {
+          self.pause_system.start(&mut self.state);
         }
       }
       GameState::Paused => {
         self
           .pause_system
           .update_state(&mut self.input, &mut self.state, &mut self.events);
+        if self.state.game_state == GameState::MainMenu {
+          self.menu_system.start(&mut self.state);
+        } else if self.state.game_state == GameState::Controls {
+          self.controls_system.start(&mut self.state);
+        }
       }
       GameState::GameOver => {
         self
@@ -129,11 +163,34 @@ impl Game for SnakeGame {
           .update_state(&mut self.input, &mut self.state, &mut self.events);
         if self.state.game_state == GameState::MainMenu {
           self.menu_system.start(&mut self.state);
+        } else if self.state.game_state == GameState::Playing {
+          self.input.clear();
+          self.play_system.start(&mut self.state);
+        }
+      }
+      GameState::HighScores => {
+        self
+          .high_score_system
+          .update_state(&mut self.input, &mut self.state, &mut self.events);
+        if self.state.game_state == GameState::MainMenu {
+          self.menu_system.start(&mut self.state);
+        }
+      }
+      GameState::Controls => {
+        self
+          .controls_system
+          .update_state(&mut self.input, &mut self.state, &mut self.events);
+        if self.state.game_state == GameState::MainMenu {
+          self.menu_system.start(&mut self.state);
+        } else if self.state.game_state == GameState::Paused {
+          self.pause_system.start(&mut self.state);
         }
       }
       GameState::Quitting => {}
     }
 
+    self.input.end_frame();
+
     geometry.reset();
     text_renderer.reset();
 
@@ -149,9 +206,18 @@ impl Game for SnakeGame {
   }
 
   fn focus_changed(&mut self, focus: bool) {
-    if !focus {
-      self.pause_system.start(&mut self.state);
+    if !focus && self.state.game_state == GameState::Playing {
       self.state.pause_game();
+      if self.state.game_state == GameState::Paused {
+        self.input.clear();
+        self.pause_system.start(&mut self.state);
+        // Keeps the run if the window is closed while in the background, but
+        // never in place of a different game the player saved earlier.
+        let replaces_save = self.state.saved_game.is_some() && !self.state.saved_run;
+        if self.state.can_save() && !replaces_save {
+          save_game(&mut self.state);
+        }
+      }
     }
   }
 }
@@ -1,8 +1,26 @@
-use crate::pellet::Pellet;
-use crate::snake::Snake;
+use crate::coords;
+use cgmath::Vector2;
 use dynamo_lib::geometry::quad::Quad;
 use dynamo_lib::geometry::Geometry;
 use dynamo_lib::renderer::render_text::{RenderText, TextRenderer, UNBOUNDED_F32};
+use snake::bindings::{Action, Bindings};
+use snake::board::Cell;
+use snake::controller::{self, Controller, BOT_NAMES};
+use snake::creature::CreatureKind;
+use snake::highscores::HighScores;
+use snake::level::Level;
+use snake::menu::Menu;
+use snake::net::Client;
+use snake::options::Options;
+use snake::pellet::PelletKind;
+use snake::power_up::PowerUpKind;
+use snake::replay::Replay;
+use snake::rules::Rules;
+use snake::save::SaveGame;
+use snake::session::Session;
+use snake::simulation::Simulation;
+use snake::ticker::Ticker;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum GameState {
@@ -10,152 +28,841 @@ pub enum GameState {
   Playing,
   Paused,
   GameOver,
+  HighScores,
+  Controls,
   Quitting,
 }
 
+/// What the main menu's items do.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MenuAction {
+  Continue,
+  Play,
+  Players,
+  Rules,
+  Level,
+  Demo,
+  Controls,
+  HighScores,
+  Quit,
+}
+
+/// What the pause menu's items do.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PauseAction {
+  Resume,
+  Restart,
+  Settings,
+  SaveAndQuit,
+  QuitToMenu,
+}
+
+/// What the game over screen's items do.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GameOverAction {
+  PlayAgain,
+  MainMenu,
+}
+
+/// What the controls screen's items do.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ControlsAction {
+  Rebind(Action),
+  Done,
+}
+
+/// What the high scores screen's items do.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HighScoresAction {
+  Back,
+}
+
+/// What the pause menu is asking the player to confirm.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PauseChoice {
+  Restart,
+  QuitToMenu,
+}
+
+/// How far right of a menu's left edge its items can be pointed at.
+const MENU_WIDTH: f32 = 400.0;
+
+/// Roughly how wide a character is, as a fraction of the font size. The text
+/// renderer can't measure text, so right-aligned text is placed by this.
+const CHAR_WIDTH: f32 = 0.55;
+
+/// A shape made of rectangles, each given as its center's offset from the
+/// middle of a cell and its size, all as fractions of the cell's size.
+type Shape = [(f32, f32, f32, f32)];
+
+/// Three small squares running corner to corner.
+const BONUS_SHAPE: [(f32, f32, f32, f32); 3] = [
+  (-0.3, -0.3, 0.35, 0.35),
+  (0.0, 0.0, 0.35, 0.35),
+  (0.3, 0.3, 0.35, 0.35),
+];
+
+/// Four small squares in the corners of the cell.
+const GHOST_SHAPE: [(f32, f32, f32, f32); 4] = [
+  (-0.3, -0.3, 0.35, 0.35),
+  (-0.3, 0.3, 0.35, 0.35),
+  (0.3, -0.3, 0.35, 0.35),
+  (0.3, 0.3, 0.35, 0.35),
+];
+
+/// Two bars across the cell.
+const SLOW_SHAPE: [(f32, f32, f32, f32); 2] = [(0.0, -0.25, 0.9, 0.25), (0.0, 0.25, 0.9, 0.25)];
+
+/// A horseshoe: two bars up the sides joined along one edge.
+const MAGNET_SHAPE: [(f32, f32, f32, f32); 3] = [
+  (-0.3, 0.0, 0.25, 0.9),
+  (0.3, 0.0, 0.25, 0.9),
+  (0.0, -0.35, 0.85, 0.2),
+];
+
+/// A bar along one edge with a block hanging from its middle.
+const SHIELD_SHAPE: [(f32, f32, f32, f32); 2] = [(0.0, 0.3, 0.9, 0.25), (0.0, -0.05, 0.5, 0.6)];
+
+/// Both ends of a portal are numbered in the same colour, taken in turn from
+/// these.
+const PORTAL_COLORS: [(f32, f32, f32, f32); 5] = [
+  (0.8, 0.3, 1.0, 1.0),
+  (0.2, 0.9, 0.9, 1.0),
+  (1.0, 0.8, 0.2, 1.0),
+  (0.3, 0.5, 1.0, 1.0),
+  (1.0, 0.4, 0.4, 1.0),
+];
+
+/// The point in the window that a piece of text is placed from.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Anchor {
+  TopLeft,
+  /// The middle of the top edge, with the text centered on it.
+  TopCenter,
+  /// The top-right corner, with the right edge of the text on it.
+  TopRight,
+  /// The middle of the window, with the text centered on it.
+  Center,
+}
+
+impl Anchor {
+  fn point(&self, window_size: (f32, f32)) -> (f32, f32) {
+    let (width, height) = window_size;
+    match self {
+      Anchor::TopLeft => (0.0, 0.0),
+      Anchor::TopCenter => (width / 2.0, 0.0),
+      Anchor::TopRight => (width, 0.0),
+      Anchor::Center => (width / 2.0, height / 2.0),
+    }
+  }
+
+  fn centered(&self) -> bool {
+    matches!(self, Anchor::TopCenter | Anchor::Center)
+  }
+}
+
 pub struct SnakeText {
   pub render_text: RenderText,
   pub visible: bool,
+  pub anchor: Anchor,
+  /// Where the text goes relative to the anchor point, in pixels.
+  pub offset: (f32, f32),
 }
 
 impl SnakeText {
-  pub fn focused(&self) -> bool {
-    self.render_text.focused
+  fn layout(&mut self, window_size: (f32, f32)) {
+    let (x, y) = self.anchor.point(window_size);
+    self.render_text.position = (x + self.offset.0, y + self.offset.1).into();
+    self.render_text.centered = self.anchor.centered();
+  }
+
+  /// The text to draw, moved left by its width if it's right-aligned.
+  fn placed(&self) -> RenderText {
+    let mut render_text = self.render_text.clone();
+    if self.anchor == Anchor::TopRight {
+      let longest = render_text
+        .text
+        .lines()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+      render_text.position.x -= longest as f32 * render_text.size * CHAR_WIDTH;
+    }
+    render_text
+  }
+}
+
+pub struct SnakeMenu<A> {
+  pub menu: Menu<A>,
+  pub visible: bool,
+  pub anchor: Anchor,
+  /// Where the first item goes relative to the anchor point, in pixels.
+  pub offset: (f32, f32),
+}
+
+impl<A: Copy + PartialEq> SnakeMenu<A> {
+  fn new(anchor: Anchor, offset: (f32, f32), menu: Menu<A>) -> Self {
+    Self {
+      menu,
+      visible: false,
+      anchor,
+      offset,
+    }
   }
 
-  pub fn set_focus(&mut self, focused: bool) {
-    self.render_text.focused = focused;
+  /// Moves the menu's rows to follow its anchor. A centered menu's rows still
+  /// span `width` from the origin, so the pointer finds them.
+  fn layout(&mut self, window_size: (f32, f32)) {
+    let (x, y) = self.anchor.point(window_size);
+    let left = if self.anchor.centered() {
+      x + self.offset.0 - self.menu.width / 2.0
+    } else {
+      x + self.offset.0
+    };
+    self.menu.origin = (left, y + self.offset.1);
+  }
+
+  /// A line of text for each item, greyed out if it's disabled.
+  fn render_texts(&self) -> Vec<RenderText> {
+    let focus = self.menu.focus_index();
+    self
+      .menu
+      .items()
+      .iter()
+      .enumerate()
+      .map(|(index, item)| RenderText {
+        position: self.item_position(index).into(),
+        color: if item.enabled {
+          (1.0, 1.0, 1.0, 1.0).into()
+        } else {
+          (0.4, 0.4, 0.4, 1.0).into()
+        },
+        text: item.label.clone(),
+        size: 32.0,
+        focused: focus == Some(index),
+        centered: self.anchor.centered(),
+        ..Default::default()
+      })
+      .collect()
+  }
+
+  fn item_position(&self, index: usize) -> (f32, f32) {
+    let (x, y) = self.menu.position(index);
+    if self.anchor.centered() {
+      (x + self.menu.width / 2.0, y)
+    } else {
+      (x, y)
+    }
   }
 }
 
 pub struct State {
   pub game_state: GameState,
-  pub walls: Vec<Quad>,
-  pub snake: Snake,
-  pub pellet: Pellet,
+  pub simulation: Simulation,
+  pub ticker: Ticker,
+  pub board_visible: bool,
+  /// The window's size in pixels, for text placed over the board.
+  window_size: (f32, f32),
+  /// Seed from the command line, used for every game instead of a random one.
+  pub fixed_seed: Option<u64>,
+  /// The local game's replay, being played back or recorded to
+  /// `record_path` when the game ends.
+  pub session: Session,
+  pub record_path: Option<PathBuf>,
+  /// Rule sets to choose from in the main menu.
+  pub rule_sets: Vec<Rules>,
+  pub rules_index: usize,
+  /// Levels to choose from in the main menu, after the plain board.
+  pub levels: Vec<Level>,
+  /// 0 is the plain board, anything else is an index into `levels` plus one.
+  pub level_index: usize,
+  /// Snakes in the next game, each steered from its own side of the
+  /// keyboard when there is more than one.
+  pub players: usize,
+  /// Connection to a game server, which runs every game instead of this one.
+  pub client: Option<Client>,
+  /// Index into `BOT_NAMES` of the bot the demo button starts.
+  pub bot_index: usize,
+  /// Bot playing the current game as an attract-mode demo, in place of input.
+  pub demo: Option<Box<dyn Controller>>,
+  pub high_scores: HighScores,
+  pub high_scores_path: Option<PathBuf>,
+  /// Name entered for the last high score, offered again for the next.
+  pub player_name: String,
+  /// The game over screen is asking for a name for a new high score.
+  pub entering_name: bool,
+  pub bindings_path: Option<PathBuf>,
+  /// Binding slot focused on the controls screen.
+  pub controls_slot: usize,
+  /// The controls screen is waiting for a key to bind.
+  pub rebinding: bool,
+  pub controls_message: String,
+  /// Where leaving the controls screen goes back to.
+  pub controls_back: GameState,
+  /// The pause menu choice waiting for confirmation.
+  pub confirming: Option<PauseChoice>,
+  /// The top score on the high score table when the game ended, 0 if the
+  /// table was empty. `None` when the game doesn't count towards the table.
+  pub best_score: Option<u32>,
+  pub save_path: Option<PathBuf>,
+  /// The game in the save file, offered by Continue in the main menu.
+  pub saved_game: Option<SaveGame>,
+  /// The game being played is the one in the save file.
+  pub saved_run: bool,
   pub title_text: SnakeText,
-  pub play_button: SnakeText,
-  pub quit_button: SnakeText,
+  pub main_menu: SnakeMenu<MenuAction>,
+  pub pause_menu: SnakeMenu<PauseAction>,
+  pub game_over_menu: SnakeMenu<GameOverAction>,
+  pub high_scores_menu: SnakeMenu<HighScoresAction>,
+  /// An item for each action, showing the keys bound to it.
+  pub controls_menu: SnakeMenu<ControlsAction>,
+  pub confirm_text: SnakeText,
   pub score: SnakeText,
+  /// Power-ups in effect and how long they have left.
+  pub effects_text: SnakeText,
   pub win_text: SnakeText,
-  // window_size: (f32, f32),
+  pub high_scores_text: SnakeText,
+  pub controls_text: SnakeText,
 }
 
 impl State {
-  pub fn new() -> Self {
+  pub fn new(options: Options, playback: Option<Replay>, client: Option<Client>) -> Self {
+    let session = Session::new(playback);
+
+    let high_scores_path = HighScores::default_path();
+    let high_scores = match &high_scores_path {
+      Some(path) => HighScores::load(path).unwrap_or_else(|error| {
+        eprintln!(
+          "Couldn't load high scores from {}: {}",
+          path.display(),
+          error
+        );
+        HighScores::default()
+      }),
+      None => HighScores::default(),
+    };
+
+    let mut rule_sets = Rules::available();
+    let rules_index = match options.rules {
+      Some(rules) => match rule_sets.iter().position(|other| *other == rules) {
+        Some(index) => index,
+        None => {
+          rule_sets.push(rules);
+          rule_sets.len() - 1
+        }
+      },
+      None => 0,
+    };
+
+    let save_path = SaveGame::default_path();
+
+    let bot_index = options
+      .bot
+      .as_deref()
+      .and_then(controller::bot)
+      .and_then(|bot| BOT_NAMES.iter().position(|name| *name == bot.name()))
+      .unwrap_or(0);
+
     Self {
       game_state: GameState::MainMenu,
-      walls: vec![
-        // Unclear why the size needs to be 4 to stretch the coordinate system
-        // x & y min are -1, x & y max are +1
-        // where does 4 come from?? All I know is 2 only goes halfway across the window
-        Quad::new((-1.0, -1.0).into(), (4.0, 0.02).into()),
-        Quad::new((-1.0, 1.0).into(), (4.0, 0.02).into()),
-        Quad::new((1.0, -1.0).into(), (0.02, 4.0).into()),
-        Quad::new((-1.0, -1.0).into(), (0.02, 4.0).into()),
-      ],
-      snake: Snake::new((0.0, 0.0).into(), (0.04, 0.04).into()),
-      pellet: Pellet::new((0.0, 0.0).into(), 0.04),
+      simulation: session.recording.simulation(),
+      ticker: Ticker::new(),
+      board_visible: false,
+      window_size: (0.0, 0.0),
+      fixed_seed: options.seed,
+      session,
+      record_path: options.record,
+      rule_sets,
+      rules_index,
+      levels: Level::available(),
+      level_index: 0,
+      players: 1,
+      client,
+      bot_index,
+      demo: None,
+      high_scores,
+      high_scores_path,
+      player_name: String::new(),
+      entering_name: false,
+      bindings_path: Bindings::default_path(),
+      controls_slot: 0,
+      rebinding: false,
+      controls_message: String::new(),
+      controls_back: GameState::MainMenu,
+      confirming: None,
+      best_score: None,
+      saved_game: save_path.as_deref().and_then(load_saved_game),
+      save_path,
+      saved_run: false,
+      main_menu: SnakeMenu::new(
+        Anchor::TopLeft,
+        (40.0, 100.0),
+        Menu::new((0.0, 0.0), 50.0, MENU_WIDTH)
+          .item("Continue", MenuAction::Continue)
+          .item("Play", MenuAction::Play)
+          .item("Players", MenuAction::Players)
+          .item("Rules", MenuAction::Rules)
+          .item("Level", MenuAction::Level)
+          .item("Demo", MenuAction::Demo)
+          .item("Controls", MenuAction::Controls)
+          .item("High Scores", MenuAction::HighScores)
+          .item("Quit", MenuAction::Quit),
+      ),
+      pause_menu: SnakeMenu::new(
+        Anchor::TopLeft,
+        (40.0, 100.0),
+        Menu::new((0.0, 0.0), 60.0, MENU_WIDTH)
+          .item("Resume", PauseAction::Resume)
+          .item("Restart", PauseAction::Restart)
+          .item("Settings", PauseAction::Settings)
+          .item("Save and Quit", PauseAction::SaveAndQuit)
+          .item("Quit to Menu", PauseAction::QuitToMenu),
+      ),
+      game_over_menu: SnakeMenu::new(
+        Anchor::TopCenter,
+        (0.0, 460.0),
+        Menu::new((0.0, 0.0), 60.0, MENU_WIDTH)
+          .item("Play Again", GameOverAction::PlayAgain)
+          .item("Main Menu", GameOverAction::MainMenu),
+      ),
+      high_scores_menu: SnakeMenu::new(
+        Anchor::TopLeft,
+        (40.0, 460.0),
+        Menu::new((0.0, 0.0), 50.0, MENU_WIDTH).item("Back", HighScoresAction::Back),
+      ),
+      controls_menu: SnakeMenu::new(
+        Anchor::TopLeft,
+        (40.0, 100.0),
+        Action::ALL
+          .iter()
+          .fold(Menu::new((0.0, 0.0), 40.0, MENU_WIDTH), |menu, action| {
+            menu.item(action.name(), ControlsAction::Rebind(*action))
+          })
+          .item("Done", ControlsAction::Done),
+      ),
       title_text: SnakeText {
         visible: false,
+        anchor: Anchor::TopLeft,
+        offset: (20.0, 20.0),
         render_text: RenderText {
-          position: (20.0, 20.0).into(),
           color: (1.0, 1.0, 1.0, 1.0).into(),
           text: String::from("SNAKE"),
           size: 64.0,
           ..Default::default()
         },
       },
-      play_button: SnakeText {
+      confirm_text: SnakeText {
         visible: false,
+        anchor: Anchor::Center,
+        offset: (0.0, -40.0),
         render_text: RenderText {
-          position: (40.0, 100.0).into(),
           color: (1.0, 1.0, 1.0, 1.0).into(),
-          text: String::from("Play"),
+          bounds: (UNBOUNDED_F32, UNBOUNDED_F32).into(),
           size: 32.0,
           ..Default::default()
         },
       },
-      quit_button: SnakeText {
+      score: SnakeText {
         visible: false,
+        anchor: Anchor::TopRight,
+        offset: (-20.0, 20.0),
         render_text: RenderText {
-          position: (40.0, 160.0).into(),
           color: (1.0, 1.0, 1.0, 1.0).into(),
-          text: String::from("Quit"),
+          text: String::from("0"),
           size: 32.0,
           ..Default::default()
         },
       },
-      score: SnakeText {
+      effects_text: SnakeText {
         visible: false,
+        anchor: Anchor::TopLeft,
+        offset: (20.0, 20.0),
         render_text: RenderText {
-          // position: (render.width() * 0.75, 20.0).into(),
-          position: (120.0, 20.0).into(),
           color: (1.0, 1.0, 1.0, 1.0).into(),
-          text: String::from("0"),
-          size: 32.0,
+          bounds: (UNBOUNDED_F32, UNBOUNDED_F32).into(),
+          size: 24.0,
           ..Default::default()
         },
       },
       win_text: SnakeText {
         visible: false,
+        anchor: Anchor::TopCenter,
+        offset: (0.0, 100.0),
         render_text: RenderText {
-          // position: (render.width() * 0.5, render.height() * 0.5).into(),
-          position: (200.0, 200.0).into(),
+          color: (1.0, 1.0, 1.0, 1.0).into(),
           bounds: (UNBOUNDED_F32, UNBOUNDED_F32).into(),
-          size: 32.0,
-          centered: true,
+          size: 24.0,
+          ..Default::default()
+        },
+      },
+      high_scores_text: SnakeText {
+        visible: false,
+        anchor: Anchor::TopLeft,
+        offset: (40.0, 100.0),
+        render_text: RenderText {
+          color: (1.0, 1.0, 1.0, 1.0).into(),
+          bounds: (UNBOUNDED_F32, UNBOUNDED_F32).into(),
+          size: 24.0,
+          ..Default::default()
+        },
+      },
+      controls_text: SnakeText {
+        visible: false,
+        anchor: Anchor::TopLeft,
+        offset: (40.0, 520.0),
+        render_text: RenderText {
+          color: (1.0, 1.0, 1.0, 1.0).into(),
+          bounds: (UNBOUNDED_F32, UNBOUNDED_F32).into(),
+          size: 24.0,
           ..Default::default()
         },
       },
     }
   }
 
-  pub fn initialize(&mut self, geometry: &mut Geometry, text_renderer: &mut TextRenderer) {
+  pub fn initialize(
+    &mut self,
+    geometry: &mut Geometry,
+    text_renderer: &mut TextRenderer,
+    window_size: (f32, f32),
+  ) {
+    self.layout(window_size);
     self.update_geometry(geometry);
     self.update_text(text_renderer);
   }
 
+  /// Lays the text out for a window of `window_size` pixels. dynamo_lib only
+  /// tells the game the window's size in `Game::initialize`, so this runs once
+  /// and the text stays where it is if the window is resized afterwards.
+  fn layout(&mut self, window_size: (f32, f32)) {
+    self.window_size = window_size;
+    for text in [
+      &mut self.title_text,
+      &mut self.confirm_text,
+      &mut self.score,
+      &mut self.effects_text,
+      &mut self.win_text,
+      &mut self.high_scores_text,
+      &mut self.controls_text,
+    ]
+    .iter_mut()
+    {
+      text.layout(window_size);
+    }
+
+    self.main_menu.layout(window_size);
+    self.pause_menu.layout(window_size);
+    self.game_over_menu.layout(window_size);
+    self.high_scores_menu.layout(window_size);
+    self.controls_menu.layout(window_size);
+  }
+
   pub fn update(&self, geometry: &mut Geometry, text_renderer: &mut TextRenderer) {
     self.update_geometry(geometry);
     self.update_text(text_renderer);
   }
 
   fn update_geometry(&self, geometry: &mut Geometry) {
-    if self.snake.visible {
-      for quad in self.walls.iter() {
-        geometry.push_quad(&quad);
+    if !self.board_visible {
+      return;
+    }
+
+    for cell in self.simulation.walls.iter() {
+      geometry.push_quad(&self.quad(*cell));
+    }
+
+    // A portal end is an empty frame, numbered by `update_text`. Each bar is
+    // its offset from the center and its size, in cells.
+    let size = coords::cell_size(&self.simulation.board);
+    let bars = [
+      ((0.0, -0.4), (1.0, 0.2)),
+      ((0.0, 0.4), (1.0, 0.2)),
+      ((-0.4, 0.0), (0.2, 1.0)),
+      ((0.4, 0.0), (0.2, 1.0)),
+    ];
+    for (a, b) in self.simulation.portals.iter() {
+      for cell in [a, b].iter() {
+        let center = coords::screen_coordinates(&self.simulation.board, **cell);
+        for ((x, y), (width, height)) in bars.iter() {
+          let offset = Vector2 {
+            x: x * size.x,
+            y: y * size.y,
+          };
+          let bar = Vector2 {
+            x: width * size.x,
+            y: height * size.y,
+          };
+          geometry.push_quad(&Quad::new(center + offset, bar));
+        }
+      }
+    }
+
+    let board = &self.simulation.board;
+    let alpha = self.ticker.alpha(self.simulation.tick_rate());
+    for (player, snake) in self.simulation.snakes.iter().enumerate() {
+      // Quads have no colour, so player two is drawn thinner to tell apart.
+      let size = match player {
+        0 => coords::cell_size(board),
+        _ => coords::cell_size(board) * 0.6,
+      };
+      for (i, cell) in snake.body.iter().enumerate() {
+        let from = snake.previous_cell(i);
+        let position = coords::interpolate(board, from, *cell, alpha);
+        geometry.push_quad(&Quad::new(position, size));
+      }
+    }
+
+    // Quads have no colour either, so pellet kinds are told apart by size,
+    // bonus pellets are three squares on a slant, and a pellet about to
+    // disappear blinks.
+    for pellet in self.simulation.pellets.iter() {
+      let blinking = pellet
+        .expires
+        .is_some_and(|tick| tick.saturating_sub(self.simulation.tick) <= 20);
+      if blinking && self.simulation.tick % 4 < 2 {
+        continue;
       }
 
-      for quad in self.snake.body.iter() {
-        geometry.push_quad(&quad);
+      let center = coords::screen_coordinates(board, pellet.cell);
+      let size = coords::cell_size(board);
+      let scale = match pellet.kind {
+        PelletKind::Bonus => {
+          push_shape(geometry, center, size, &BONUS_SHAPE);
+          continue;
+        }
+        PelletKind::Normal => 1.0,
+        PelletKind::Speed => 0.8,
+        PelletKind::Shrink => 0.6,
+        PelletKind::Poison => 0.4,
+      };
+      geometry.push_quad(&Quad::new(center, size * scale));
+    }
+
+    // Creatures are told apart by shape: a patrol is a solid block, a chaser
+    // a cross and a mouse a pair of small squares.
+    for creature in self.simulation.creatures.iter() {
+      let center = coords::interpolate(board, creature.previous, creature.cell, alpha);
+      let size = coords::cell_size(board);
+      match creature.kind {
+        CreatureKind::Patrol => geometry.push_quad(&Quad::new(center, size)),
+        CreatureKind::Mouse => {
+          for x in [-0.2, 0.2].iter() {
+            let offset = Vector2 {
+              x: x * size.x,
+              y: 0.0,
+            };
+            geometry.push_quad(&Quad::new(center + offset, size * 0.3));
+          }
+        }
+        CreatureKind::Chaser => {
+          let across = Vector2 {
+            x: size.x * 0.9,
+            y: size.y * 0.25,
+          };
+          let down = Vector2 {
+            x: size.x * 0.25,
+            y: size.y * 0.9,
+          };
+          geometry.push_quad(&Quad::new(center, across));
+          geometry.push_quad(&Quad::new(center, down));
+        }
       }
     }
 
-    if self.pellet.visible {
-      geometry.push_quad(&self.pellet.quad);
+    // Each kind of power-up has a shape of its own.
+    if let Some(power_up) = &self.simulation.power_up {
+      let shape: &Shape = match power_up.kind {
+        PowerUpKind::Ghost => &GHOST_SHAPE,
+        PowerUpKind::Slow => &SLOW_SHAPE,
+        PowerUpKind::Magnet => &MAGNET_SHAPE,
+        PowerUpKind::Shield => &SHIELD_SHAPE,
+      };
+      let center = coords::screen_coordinates(board, power_up.cell);
+      push_shape(geometry, center, coords::cell_size(board), shape);
     }
   }
 
+  fn quad(&self, cell: Cell) -> Quad {
+    let board = &self.simulation.board;
+    Quad::new(
+      coords::screen_coordinates(board, cell),
+      coords::cell_size(board),
+    )
+  }
+
   fn update_text(&self, text_renderer: &mut TextRenderer) {
-    for text in vec![
+    for text in [
       &self.title_text,
-      &self.play_button,
-      &self.quit_button,
+      &self.confirm_text,
       &self.score,
+      &self.effects_text,
       &self.win_text,
+      &self.high_scores_text,
+      &self.controls_text,
     ]
     .iter()
     {
       if text.visible {
-        text_renderer.push_render_text(text.render_text.clone());
+        text_renderer.push_render_text(text.placed());
+      }
+    }
+
+    let mut menus = Vec::new();
+    if self.main_menu.visible {
+      menus.extend(self.main_menu.render_texts());
+    }
+    if self.pause_menu.visible {
+      menus.extend(self.pause_menu.render_texts());
+    }
+    if self.game_over_menu.visible {
+      menus.extend(self.game_over_menu.render_texts());
+    }
+    if self.high_scores_menu.visible {
+      menus.extend(self.high_scores_menu.render_texts());
+    }
+    if self.controls_menu.visible {
+      menus.extend(self.controls_menu.render_texts());
+    }
+    for text in menus {
+      text_renderer.push_render_text(text);
+    }
+
+    if self.board_visible {
+      for text in self.portal_texts() {
+        text_renderer.push_render_text(text);
       }
     }
   }
 
+  /// The number of each portal over both of its ends.
+  fn portal_texts(&self) -> Vec<RenderText> {
+    let board = &self.simulation.board;
+    let size = self.window_size.1 / board.height as f32 * 0.8;
+    let mut texts = Vec::new();
+
+    for (pair, (a, b)) in self.simulation.portals.iter().enumerate() {
+      for cell in [a, b].iter() {
+        let (x, y) = coords::pixel_coordinates(board, **cell, self.window_size);
+        texts.push(RenderText {
+          position: (x, y - size / 2.0).into(),
+          bounds: (UNBOUNDED_F32, UNBOUNDED_F32).into(),
+          color: PORTAL_COLORS[pair % PORTAL_COLORS.len()].into(),
+          text: (pair + 1).to_string(),
+          size,
+          centered: true,
+          ..Default::default()
+        });
+      }
+    }
+    texts
+  }
+
+  pub fn rules(&self) -> &Rules {
+    &self.rule_sets[self.rules_index]
+  }
+
+  /// The selected level, or `None` for the plain board.
+  pub fn level(&self) -> Option<&Level> {
+    match self.level_index {
+      0 => None,
+      index => Some(&self.levels[index - 1]),
+    }
+  }
+
+  pub fn level_name(&self) -> &str {
+    self.level().map_or("Open", |level| &level.name)
+  }
+
+  /// Whether the game is a versus game between players at the keyboard.
+  pub fn versus(&self) -> bool {
+    self.players > 1 && self.session.playback.is_none() && self.demo.is_none() && !self.online()
+  }
+
+  /// Whether games are played on a server.
+  pub fn online(&self) -> bool {
+    self.client.is_some() && self.demo.is_none()
+  }
+
+  /// The snake this player steers in an online game, `None` while waiting for
+  /// a round to start.
+  pub fn online_player(&self) -> Option<usize> {
+    self.client.as_ref().and_then(|client| client.player)
+  }
+
+  pub fn bot_name(&self) -> &'static str {
+    BOT_NAMES[self.bot_index]
+  }
+
+  /// The high score table for the selected rule set and level.
+  pub fn high_score_key(&self) -> String {
+    HighScores::key(&self.rules().name, self.level_name())
+  }
+
+  /// Whether the game being played can be saved, which only single-player
+  /// games at the keyboard can.
+  pub fn can_save(&self) -> bool {
+    self.session.playback.is_none() && self.demo.is_none() && !self.online() && !self.versus()
+  }
+
+  /// Selects `rules` and `level` in the main menu, adding them to the choices
+  /// if they aren't there.
+  pub fn select(&mut self, rules: &Rules, level: Option<&Level>) {
+    self.rules_index = match self.rule_sets.iter().position(|other| other == rules) {
+      Some(index) => index,
+      None => {
+        self.rule_sets.push(rules.clone());
+        self.rule_sets.len() - 1
+      }
+    };
+
+    self.level_index = match level {
+      None => 0,
+      Some(level) => match self.levels.iter().position(|other| other == level) {
+        Some(index) => index + 1,
+        None => {
+          self.levels.push(level.clone());
+          self.levels.len()
+        }
+      },
+    };
+  }
+
   pub fn pause_game(&mut self) {
-    if self.game_state == GameState::Playing {
+    // The server doesn't stop for one player, so online games can't pause.
+    if self.game_state == GameState::Playing && !self.online() {
       self.game_state = GameState::Paused;
     }
   }
 }
+
+/// The game saved at `path`, if there is one that still plays back.
+fn load_saved_game(path: &Path) -> Option<SaveGame> {
+  let saved_game = SaveGame::load(path).and_then(|save| match save {
+    Some(save) => save.resume().map(|_| Some(save)),
+    None => Ok(None),
+  });
+
+  saved_game.unwrap_or_else(|error| {
+    eprintln!(
+      "Couldn't load saved game from {}: {}",
+      path.display(),
+      error
+    );
+    None
+  })
+}
+
+/// Draws `shape` over a cell of `size` centred on `center`.
+fn push_shape(geometry: &mut Geometry, center: Vector2<f32>, size: Vector2<f32>, shape: &Shape) {
+  for (x, y, width, height) in shape.iter() {
+    let offset = Vector2 {
+      x: x * size.x,
+      y: y * size.y,
+    };
+    let part = Vector2 {
+      x: width * size.x,
+      y: height * size.y,
+    };
+    geometry.push_quad(&Quad::new(center + offset, part));
+  }
+}
@@ -1,11 +1,17 @@
-use crate::any;
-use crate::coords;
 use crate::input::Input;
-use crate::snake_game::*;
 use crate::state::*;
-use crate::util;
-use crate::util::Direction::*;
-use rand::Rng;
+use snake::any;
+use snake::bindings::{Action, SLOTS};
+use snake::controller;
+use snake::creature::CreatureKind;
+use snake::event::Event;
+use snake::highscores::{self, HighScore};
+use snake::menu::Menu;
+use snake::net::ClientMessage;
+use snake::save::SaveGame;
+use snake::simulation::Simulation;
+use snake::snake::Crash;
+use snake::util::Direction;
 
 pub trait System {
   #[allow(unused_variables)]
@@ -17,17 +23,31 @@ pub struct VisibilitySystem;
 impl System for VisibilitySystem {
   fn update_state(&self, _input: &mut Input, state: &mut State, _events: &mut Vec<Event>) {
     let is_in_game = any!(state.game_state, GameState::Playing, GameState::GameOver);
-    state.snake.visible = is_in_game;
+    state.board_visible = is_in_game && !(state.online() && state.online_player().is_none());
     state.score.visible = is_in_game;
-    state.pellet.visible = is_in_game;
-
-    state.title_text.visible =
-      state.game_state == GameState::MainMenu || state.game_state == GameState::Paused;
-    state.play_button.visible =
-      state.game_state == GameState::MainMenu || state.game_state == GameState::Paused;
-    state.quit_button.visible = state.game_state == GameState::MainMenu;
-
-    state.win_text.visible = state.game_state == GameState::GameOver;
+    state.effects_text.visible = state.board_visible && state.game_state == GameState::Playing;
+
+    state.title_text.visible = any!(
+      state.game_state,
+      GameState::MainMenu,
+      GameState::Paused,
+      GameState::GameOver,
+      GameState::HighScores,
+      GameState::Controls
+    );
+    state.main_menu.visible = state.game_state == GameState::MainMenu;
+
+    let is_paused = state.game_state == GameState::Paused;
+    state.pause_menu.visible = is_paused && state.confirming.is_none();
+    state.confirm_text.visible = is_paused && state.confirming.is_some();
+
+    let is_game_over = state.game_state == GameState::GameOver;
+    state.win_text.visible = is_game_over;
+    state.game_over_menu.visible = is_game_over && !state.entering_name;
+    state.high_scores_text.visible = state.game_state == GameState::HighScores;
+    state.high_scores_menu.visible = state.game_state == GameState::HighScores;
+    state.controls_text.visible = state.game_state == GameState::Controls;
+    state.controls_menu.visible = state.game_state == GameState::Controls;
   }
 }
 
@@ -37,10 +57,13 @@ pub struct MenuSystem;
 impl System for MenuSystem {
   fn start(&mut self, state: &mut State) {
     state.title_text.render_text.text = String::from("SNAKE");
-    state.play_button.render_text.text = String::from("Play");
+    state.demo = None;
+    update_main_menu(state);
 
-    state.play_button.render_text.focused = true;
-    state.quit_button.render_text.focused = false;
+    let menu = &mut state.main_menu.menu;
+    if !menu.focus(MenuAction::Continue) {
+      menu.focus(MenuAction::Play);
+    }
   }
 
   fn update_state(&self, input: &mut Input, state: &mut State, events: &mut Vec<Event>) {
@@ -51,106 +74,298 @@ impl System for MenuSystem {
       input.esc_pressed = false;
     }
 
-    if state.play_button.focused() && input.ui_down_pressed() {
-      events.push(Event::FocusChanged);
-      state.play_button.set_focus(false);
-      state.quit_button.set_focus(true);
-    } else if state.quit_button.focused() && input.ui_up_pressed() {
+    let chosen = choose(&mut state.main_menu.menu, input, events);
+
+    if state.main_menu.menu.focused() == Some(MenuAction::Demo)
+      && (input.left_pressed || input.right_pressed)
+    {
       events.push(Event::FocusChanged);
-      state.quit_button.set_focus(false);
-      state.play_button.set_focus(true);
+      let count = controller::BOT_NAMES.len();
+      state.bot_index = if input.left_pressed {
+        (state.bot_index + count - 1) % count
+      } else {
+        (state.bot_index + 1) % count
+      };
     }
-
-    if state.play_button.focused() && input.enter_pressed {
-      events.push(Event::ButtonPressed);
-      state.game_state = GameState::Playing;
-    } else if state.quit_button.focused() && input.enter_pressed {
-      events.push(Event::ButtonPressed);
-      state.game_state = GameState::Quitting;
+    input.left_pressed = false;
+    input.right_pressed = false;
+
+    match chosen {
+      Some(MenuAction::Continue) => continue_game(state),
+      Some(MenuAction::Play) => state.game_state = GameState::Playing,
+      Some(MenuAction::Players) => state.players = state.players % MAX_PLAYERS + 1,
+      Some(MenuAction::Rules) => {
+        state.rules_index = (state.rules_index + 1) % state.rule_sets.len();
+      }
+      Some(MenuAction::Level) => {
+        state.level_index = (state.level_index + 1) % (state.levels.len() + 1);
+      }
+      Some(MenuAction::Demo) => {
+        state.demo = controller::bot(state.bot_name());
+        state.game_state = GameState::Playing;
+      }
+      Some(MenuAction::Controls) => {
+        state.controls_back = GameState::MainMenu;
+        state.game_state = GameState::Controls;
+      }
+      Some(MenuAction::HighScores) => state.game_state = GameState::HighScores,
+      Some(MenuAction::Quit) => state.game_state = GameState::Quitting,
+      None => {}
     }
+
+    update_main_menu(state);
+  }
+}
+
+/// Moves a menu's focus with the up and down keys. Returns the focused item's
+/// action when Return is pressed.
+fn choose<A: Copy + PartialEq>(
+  menu: &mut Menu<A>,
+  input: &mut Input,
+  events: &mut Vec<Event>,
+) -> Option<A> {
+  let moved = if input.ui_down_pressed() {
+    menu.focus_next()
+  } else if input.ui_up_pressed() {
+    menu.focus_previous()
+  } else {
+    false
+  };
+  if moved {
+    events.push(Event::FocusChanged);
+  }
+  input.up_pressed = false;
+  input.down_pressed = false;
+
+  let chosen = if input.enter_pressed {
+    menu.focused()
+  } else {
+    None
+  };
+  if chosen.is_some() {
+    events.push(Event::ButtonPressed);
   }
+  input.enter_pressed = false;
+
+  chosen
+}
+
+/// Shows the current choices in the main menu's labels.
+fn update_main_menu(state: &mut State) {
+  let labels = [
+    (MenuAction::Players, format!("Players: {}", state.players)),
+    (MenuAction::Rules, format!("Rules: {}", state.rules().name)),
+    (MenuAction::Level, format!("Level: {}", state.level_name())),
+    (MenuAction::Demo, format!("Demo: {}", state.bot_name())),
+  ];
+
+  let menu = &mut state.main_menu.menu;
+  for (action, label) in labels.iter() {
+    menu.set_label(*action, label.clone());
+  }
+  menu.set_enabled(MenuAction::Continue, state.saved_game.is_some());
 }
 
+/// Players that fit on one keyboard.
+const MAX_PLAYERS: usize = 2;
+
 #[derive(Debug)]
 pub struct PlaySystem;
 
 impl System for PlaySystem {
   fn start(&mut self, state: &mut State) {
-    state.snake.reset();
+    if state.online() {
+      start_online(state);
+      return;
+    }
 
-    let random_position = self.random_position(state);
-    state.pellet.update_position(random_position.into())
+    let players = if state.versus() { state.players } else { 1 };
+    let rules = state.rules().clone();
+    let level = state.level().cloned();
+    state.simulation = state
+      .session
+      .start(state.fixed_seed, &rules, level.as_ref(), players);
+    state.saved_run = false;
+    state.ticker.reset();
   }
 
   fn update_state(&self, input: &mut Input, state: &mut State, events: &mut Vec<Event>) {
-    if input.esc_pressed {
+    // Demos and online games can't pause, so leaving them goes straight back
+    // to the menu.
+    let can_pause = state.demo.is_none() && !state.online();
+    if (input.esc_pressed && !can_pause) || (state.demo.is_some() && input.enter_pressed) {
       input.clear();
       events.push(Event::ButtonPressed);
       state.game_state = GameState::MainMenu;
+      leave_online(state);
+      return;
+    }
 
-      input.esc_pressed = false;
+    if state.online() {
+      update_online(input, state, events);
+      return;
     }
 
-    state.score.render_text.text = format!("Score: {}", state.snake.score);
+    if input.pause_pressed || input.esc_pressed {
+      input.clear();
+      events.push(Event::ButtonPressed);
+      state.pause_game();
+      return;
+    }
 
-    state
-      .snake
-      .update_position(state.snake.position() + state.snake.direction() * state.snake.speed);
+    state.score.render_text.text = score_text(&state.simulation);
+    state.effects_text.render_text.text = effects_text(&state.simulation);
+
+    let ticks = state.ticker.update(state.simulation.tick_rate());
+    for _ in 0..ticks {
+      if state.versus() {
+        let turns: Vec<Vec<Direction>> = state
+          .simulation
+          .snakes
+          .iter()
+          .enumerate()
+          .map(|(player, snake)| {
+            input
+              .next_player_turn(player, snake.travelled)
+              .into_iter()
+              .collect()
+          })
+          .collect();
+        let turns: Vec<&[Direction]> = turns.iter().map(Vec::as_slice).collect();
+        events.extend(state.simulation.step_players(&turns));
+
+        if state.simulation.game_over() {
+          state.game_state = GameState::GameOver;
+          break;
+        }
+        continue;
+      }
 
-    if input.up_pressed && !matches!(state.snake.direction, Down) {
-      state.snake.update_direction(Up);
-    }
-    if input.down_pressed && !matches!(state.snake.direction, Up) {
-      state.snake.update_direction(Down);
-    }
-    if input.right_pressed && !matches!(state.snake.direction, Left) {
-      state.snake.update_direction(Right);
-    }
-    if input.left_pressed && !matches!(state.snake.direction, Right) {
-      state.snake.update_direction(Left);
-    }
+      let demo = &mut state.demo;
+      let step_events = state
+        .session
+        .step(&mut state.simulation, |simulation| match demo {
+          Some(bot) => bot.next_turn(simulation),
+          None => input.next_turn(simulation.snakes[0].travelled),
+        });
+      events.extend(step_events);
 
-    for quad in state.walls.iter() {
-      if state.snake.intersects(quad) {
-        events.push(Event::SnakeCrashed);
+      if state.session.finished(&state.simulation) {
         state.game_state = GameState::GameOver;
+        break;
       }
     }
+  }
+}
+
+/// Asks the server for a place in the next round.
+fn start_online(state: &mut State) {
+  if let Some(client) = &mut state.client {
+    client.player = None;
+    if let Err(error) = client.send(ClientMessage::Ready) {
+      lose_connection(state, error);
+      return;
+    }
+  }
+  state.ticker.reset();
+}
+
+/// Tells the server the player no longer wants a place in the next round.
+fn leave_online(state: &mut State) {
+  if !state.online() {
+    return;
+  }
 
-    let body_after_head = &state.snake.body[1..];
+  if let Some(client) = &mut state.client {
+    if let Err(error) = client.send(ClientMessage::Unready) {
+      lose_connection(state, error);
+    }
+  }
+}
 
-    for quad in body_after_head.iter() {
-      if state.snake.collides(quad) {
-        events.push(Event::SnakeCrashed);
-        state.game_state = GameState::GameOver;
+/// Sends the player's turns to the server and steps the game with the ticks
+/// that come back.
+fn update_online(input: &mut Input, state: &mut State, events: &mut Vec<Event>) {
+  let client = match &mut state.client {
+    Some(client) => client,
+    None => return,
+  };
+
+  if let Some(player) = client.player {
+    let travelled = state.simulation.snakes[player].travelled;
+    if let Some(turn) = input.next_turn(travelled) {
+      if let Err(error) = client.send(ClientMessage::Turn(turn)) {
+        lose_connection(state, error);
+        return;
       }
     }
+  }
 
-    if state.snake.collides(&state.pellet.quad) {
-      state.snake.score = state.snake.score + 1;
-      events.push(Event::Score(state.snake.score));
+  match client.update(&mut state.simulation) {
+    Ok((changed, step_events)) => {
+      events.extend(step_events);
+      if changed {
+        state.ticker.reset();
+      }
+    }
+    Err(error) => {
+      lose_connection(state, error);
+      return;
+    }
+  }
+  state.ticker.update(state.simulation.tick_rate());
 
-      state.snake.grow_body();
-      state.snake.speed += util::SNAKE_SPEED_INC;
+  state.score.render_text.text = match state.online_player() {
+    Some(player) => format!("{}   You: P{}", score_text(&state.simulation), player + 1),
+    None => String::from("Waiting for players"),
+  };
+  state.effects_text.render_text.text = effects_text(&state.simulation);
 
-      let random_position = self.random_position(state);
-      state.pellet.update_position(random_position.into());
-    }
+  if state.online_player().is_some() && state.simulation.game_over() {
+    state.game_state = GameState::GameOver;
   }
 }
 
-impl PlaySystem {
-  fn random_position(&self, state: &mut State) -> cgmath::Vector2<f32> {
-    let mut rng = rand::thread_rng();
+/// Drops the server connection and goes back to playing locally.
+fn lose_connection(state: &mut State, error: std::io::Error) {
+  eprintln!("Lost connection to the server: {}", error);
+  state.client = None;
+  state.game_state = GameState::MainMenu;
+}
 
-    let limit_x = (1.0 / state.snake.segment_size.x).round() as i32 - 1;
-    let limit_y = (1.0 / state.snake.segment_size.y).round() as i32 - 1;
+fn score_text(simulation: &Simulation) -> String {
+  let score = match simulation.snakes.as_slice() {
+    [snake] => format!("Score: {}", snake.score),
+    snakes => snakes
+      .iter()
+      .enumerate()
+      .map(|(player, snake)| format!("P{}: {}", player + 1, snake.score))
+      .collect::<Vec<_>>()
+      .join("   "),
+  };
+
+  match simulation.pellets_left() {
+    Some(left) => format!("{}\nPellets left: {}", score, left),
+    None => score,
+  }
+}
 
-    let rand_x = rng.gen_range(-limit_x..limit_x);
-    let rand_y = rng.gen_range(-limit_y..limit_y);
+/// Each power-up in effect with the seconds it has left, one per line.
+fn effects_text(simulation: &Simulation) -> String {
+  let versus = simulation.snakes.len() > 1;
+  let rate = simulation.tick_rate();
+  let mut text = String::new();
 
-    coords::screen_coordinates(state.snake.segment_size, (rand_x, rand_y))
+  for (player, snake) in simulation.snakes.iter().enumerate() {
+    for effect in snake.effects.iter() {
+      if versus {
+        text.push_str(&format!("P{} ", player + 1));
+      }
+      let seconds = effect.until.saturating_sub(simulation.tick) as f32 / rate;
+      text.push_str(&format!("{} {:.1}s\n", effect.kind.name(), seconds));
+    }
   }
+  text
 }
 
 #[derive(Debug)]
@@ -159,49 +374,475 @@ pub struct PauseSystem;
 impl System for PauseSystem {
   fn start(&mut self, state: &mut State) {
     state.title_text.render_text.text = String::from("Paused");
-    state.play_button.render_text.text = String::from("Resume");
-    state.play_button.render_text.focused = true;
+    state.confirming = None;
+
+    let can_save = state.can_save();
+    let menu = &mut state.pause_menu.menu;
+    menu.set_enabled(PauseAction::SaveAndQuit, can_save);
+    menu.focus(PauseAction::Resume);
   }
 
   fn update_state(&self, input: &mut Input, state: &mut State, events: &mut Vec<Event>) {
-    if state.play_button.focused() && input.enter_pressed {
+    if let Some(choice) = state.confirming {
+      self.confirm(choice, input, state, events);
+      return;
+    }
+
+    if input.pause_pressed || input.esc_pressed {
+      events.push(Event::ButtonPressed);
+      resume(input, state);
+      return;
+    }
+
+    match choose(&mut state.pause_menu.menu, input, events) {
+      Some(PauseAction::Resume) => resume(input, state),
+      Some(PauseAction::Restart) => state.confirming = Some(PauseChoice::Restart),
+      Some(PauseAction::Settings) => {
+        state.controls_back = GameState::Paused;
+        state.game_state = GameState::Controls;
+      }
+      Some(PauseAction::SaveAndQuit) => {
+        save_game(state);
+        state.game_state = GameState::MainMenu;
+      }
+      Some(PauseAction::QuitToMenu) => state.confirming = Some(PauseChoice::QuitToMenu),
+      None => {}
+    }
+
+    update_confirm_text(input, state);
+  }
+}
+
+impl PauseSystem {
+  /// Asks whether to give up the run before restarting or quitting.
+  fn confirm(
+    &self,
+    choice: PauseChoice,
+    input: &mut Input,
+    state: &mut State,
+    events: &mut Vec<Event>,
+  ) {
+    if input.enter_pressed {
+      events.push(Event::ButtonPressed);
+      state.confirming = None;
+      discard_save(state);
+      match choice {
+        PauseChoice::Restart => {
+          PlaySystem.start(state);
+          resume(input, state);
+        }
+        PauseChoice::QuitToMenu => state.game_state = GameState::MainMenu,
+      }
+      input.clear();
+    } else if input.esc_pressed {
       events.push(Event::ButtonPressed);
-      state.game_state = GameState::Playing;
+      state.confirming = None;
+      input.clear();
     }
+
+    update_confirm_text(input, state);
   }
 }
 
-pub struct GameOverSystem {
-  last_time: std::time::Instant,
+/// Writes the game being played to the save file.
+pub fn save_game(state: &mut State) {
+  let save = SaveGame::new(&state.session.recording, &state.simulation);
+  if let Some(path) = &state.save_path {
+    if let Err(error) = save.save(path) {
+      eprintln!("Couldn't save the game to {}: {}", path.display(), error);
+      return;
+    }
+  }
+
+  state.saved_game = Some(save);
+  state.saved_run = true;
 }
 
-impl GameOverSystem {
-  pub fn new() -> Self {
-    Self {
-      last_time: std::time::Instant::now(),
+/// Deletes the save file once the game in it is over or abandoned.
+fn discard_save(state: &mut State) {
+  if !state.saved_run {
+    return;
+  }
+
+  if let Some(path) = &state.save_path {
+    if let Err(error) = SaveGame::remove(path) {
+      eprintln!(
+        "Couldn't delete the saved game {}: {}",
+        path.display(),
+        error
+      );
+    }
+  }
+  state.saved_game = None;
+  state.saved_run = false;
+}
+
+/// Picks the saved game back up, paused so the player can get ready.
+fn continue_game(state: &mut State) {
+  let save = match &state.saved_game {
+    Some(save) => save.clone(),
+    None => return,
+  };
+
+  match save.resume() {
+    Ok(simulation) => {
+      state.select(&save.game.rules, save.game.level.as_ref());
+      state.players = 1;
+      state.simulation = simulation;
+      state.session.recording = save.game;
+      state.saved_run = true;
+      state.score.render_text.text = score_text(&state.simulation);
+      state.ticker.reset();
+      state.game_state = GameState::Paused;
+    }
+    Err(error) => {
+      eprintln!("Couldn't continue the saved game: {}", error);
+      state.saved_game = None;
+    }
+  }
+}
+
+fn resume(input: &mut Input, state: &mut State) {
+  state.game_state = GameState::Playing;
+  state.ticker.reset();
+  input.clear();
+}
+
+fn update_confirm_text(input: &Input, state: &mut State) {
+  let question = match state.confirming {
+    Some(PauseChoice::Restart) => "Restart and lose this run?",
+    Some(PauseChoice::QuitToMenu) => "Quit to the menu and lose this run?",
+    None => {
+      state.confirm_text.render_text.text = String::new();
+      return;
     }
+  };
+
+  state.confirm_text.render_text.text = format!(
+    "{}\n{}: yes   {}: no",
+    question,
+    key_names(input, Action::Confirm),
+    key_names(input, Action::Back)
+  );
+}
+
+/// The keys bound to `action`, as the player would read them in a hint.
+fn key_names(input: &Input, action: Action) -> String {
+  match input.bindings.keys(action) {
+    [] => String::from("-"),
+    keys => keys.join("/"),
   }
 }
 
+#[derive(Debug)]
+pub struct GameOverSystem;
+
 impl System for GameOverSystem {
   fn start(&mut self, state: &mut State) {
-    self.last_time = std::time::Instant::now();
+    discard_save(state);
+    // Replays only hold one player's turns, so versus games aren't saved, and
+    // demos would overwrite the player's own recording.
+    let multiplayer = state.versus() || state.online();
+    if let (Some(path), false) = (&state.record_path, multiplayer || state.demo.is_some()) {
+      if let Err(error) = state.session.save_recording(&state.simulation, path) {
+        eprintln!("Couldn't save replay to {}: {}", path.display(), error);
+      }
+    }
 
-    state.win_text.render_text.text = String::from("Game Over")
+    let key = state.high_score_key();
+    let scored = state.session.playback.is_none() && state.demo.is_none() && !multiplayer;
+    state.best_score = if scored {
+      let entries = state.high_scores.entries(&key);
+      Some(entries.first().map_or(0, |best| best.score))
+    } else {
+      None
+    };
+    state.entering_name = scored
+      && state
+        .high_scores
+        .qualifies(&key, state.simulation.snakes[0].score);
+
+    state.game_over_menu.menu.focus_first();
+
+    update_game_over_text(state);
   }
 
   fn update_state(&self, input: &mut Input, state: &mut State, events: &mut Vec<Event>) {
+    if state.entering_name {
+      self.enter_name(input, state, events);
+      return;
+    }
+
     if input.esc_pressed {
       events.push(Event::ButtonPressed);
-      state.game_state = GameState::Quitting;
+      state.game_state = GameState::MainMenu;
+
+      input.esc_pressed = false;
+      return;
+    }
+
+    match choose(&mut state.game_over_menu.menu, input, events) {
+      Some(GameOverAction::PlayAgain) => state.game_state = GameState::Playing,
+      Some(GameOverAction::MainMenu) => state.game_state = GameState::MainMenu,
+      None => {}
+    }
+  }
+}
+
+impl GameOverSystem {
+  fn enter_name(&self, input: &mut Input, state: &mut State, events: &mut Vec<Event>) {
+    for character in input.typed.chars() {
+      if state.player_name.len() < MAX_NAME_LENGTH {
+        state.player_name.push(character);
+      }
+    }
+    if input.back_pressed {
+      state.player_name.pop();
+    }
+
+    if input.enter_pressed && !state.player_name.trim().is_empty() {
+      events.push(Event::ButtonPressed);
+      save_high_score(state);
+      state.entering_name = false;
+
+      input.enter_pressed = false;
+    } else if input.esc_pressed {
+      events.push(Event::ButtonPressed);
+      state.entering_name = false;
 
       input.esc_pressed = false;
     }
 
-    let current_time = std::time::Instant::now();
-    let delta_time = current_time - self.last_time;
-    if delta_time.as_secs_f32() > 5.0 {
+    update_game_over_text(state);
+  }
+}
+
+const MAX_NAME_LENGTH: usize = 12;
+
+fn update_game_over_text(state: &mut State) {
+  let simulation = &state.simulation;
+  state.title_text.render_text.text = if simulation.snakes.len() > 1 {
+    match simulation.winner() {
+      Some(player) if state.online_player() == Some(player) => String::from("You Win"),
+      Some(player) => format!("Player {} Wins", player + 1),
+      None => String::from("Draw"),
+    }
+  } else if simulation.won {
+    String::from("You Win")
+  } else {
+    String::from("Game Over")
+  };
+
+  let seconds = simulation.elapsed as u32;
+  let mut text = format!(
+    "Time: {}:{:02}\nSeed: {}\n",
+    seconds / 60,
+    seconds % 60,
+    simulation.seed
+  );
+  let minutes = simulation.elapsed / 60.0;
+  for (player, snake) in simulation.snakes.iter().enumerate() {
+    text.push('\n');
+    if simulation.snakes.len() > 1 {
+      text.push_str(&format!("Player {}\n", player + 1));
+    }
+    text.push_str(&format!("Score: {}\n", snake.score));
+    text.push_str(&format!("Length: {}\n", snake.body.len()));
+    if minutes > 0.0 {
+      text.push_str(&format!(
+        "Pellets per minute: {:.1}\n",
+        snake.eaten as f32 / minutes
+      ));
+    }
+    if let Some(crash) = snake.crash {
+      text.push_str(&format!("Died: {}\n", crash_text(crash)));
+    }
+  }
+
+  let score = simulation.snakes[0].score;
+  match state.best_score {
+    Some(best) if score > best => text.push_str("New best!\n"),
+    Some(best) => text.push_str(&format!("Best: {}\n", best)),
+    None => {}
+  }
+  if state.entering_name {
+    text.push_str(&format!("New High Score!\nName: {}_", state.player_name));
+  }
+  state.win_text.render_text.text = text;
+}
+
+fn crash_text(crash: Crash) -> &'static str {
+  match crash {
+    Crash::Border => "ran off the board",
+    Crash::Wall => "hit a wall",
+    Crash::Itself => "ran into itself",
+    Crash::Snake => "ran into another snake",
+    Crash::HeadOn => "head-on collision",
+    Crash::Creature(CreatureKind::Patrol) => "hit a patrol",
+    Crash::Creature(CreatureKind::Chaser) => "caught by a chaser",
+    Crash::Creature(CreatureKind::Mouse) => "ran into a mouse",
+  }
+}
+
+fn save_high_score(state: &mut State) {
+  let simulation = &state.simulation;
+  let entry = HighScore {
+    name: String::from(state.player_name.trim()),
+    score: simulation.snakes[0].score,
+    length: simulation.snakes[0].body.len() as u32,
+    seconds: simulation.elapsed,
+    date: highscores::today(),
+  };
+
+  let key = state.high_score_key();
+  state.high_scores.insert(&key, entry);
+
+  if let Some(path) = &state.high_scores_path {
+    if let Err(error) = state.high_scores.save(path) {
+      eprintln!("Couldn't save high scores to {}: {}", path.display(), error);
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct HighScoreSystem;
+
+impl System for HighScoreSystem {
+  fn start(&mut self, state: &mut State) {
+    let key = state.high_score_key();
+    state.title_text.render_text.text = String::from("High Scores");
+
+    let entries = state.high_scores.entries(&key);
+    let mut text = format!("{}\n\n", key);
+    if entries.is_empty() {
+      text.push_str("No scores yet");
+    }
+    for (i, entry) in entries.iter().enumerate() {
+      let seconds = entry.seconds as u32;
+      text.push_str(&format!(
+        "{:>2}. {:<12} {:>5}  length {:<4} {}:{:02}  {}\n",
+        i + 1,
+        entry.name,
+        entry.score,
+        entry.length,
+        seconds / 60,
+        seconds % 60,
+        entry.date
+      ));
+    }
+    state.high_scores_text.render_text.text = text;
+    state.high_scores_menu.menu.focus_first();
+  }
+
+  fn update_state(&self, input: &mut Input, state: &mut State, events: &mut Vec<Event>) {
+    if input.esc_pressed {
+      events.push(Event::ButtonPressed);
       state.game_state = GameState::MainMenu;
+
+      input.esc_pressed = false;
+      return;
     }
+
+    match choose(&mut state.high_scores_menu.menu, input, events) {
+      Some(HighScoresAction::Back) => state.game_state = GameState::MainMenu,
+      None => {}
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct ControlsSystem;
+
+impl System for ControlsSystem {
+  fn start(&mut self, state: &mut State) {
+    state.title_text.render_text.text = String::from("Controls");
+    state.controls_menu.menu.focus_first();
+    state.controls_slot = 0;
+    state.rebinding = false;
+    state.controls_message = String::new();
   }
+
+  fn update_state(&self, input: &mut Input, state: &mut State, events: &mut Vec<Event>) {
+    if state.rebinding {
+      if let Some(name) = input.pressed_key {
+        events.push(Event::ButtonPressed);
+        self.rebind(input, state, name);
+        input.clear();
+      }
+      update_controls_text(input, state);
+      return;
+    }
+
+    if input.esc_pressed {
+      events.push(Event::ButtonPressed);
+      state.game_state = state.controls_back;
+
+      input.clear();
+      return;
+    }
+
+    if input.left_pressed || input.right_pressed {
+      events.push(Event::FocusChanged);
+      state.controls_slot = (state.controls_slot + 1) % SLOTS;
+    }
+
+    match choose(&mut state.controls_menu.menu, input, events) {
+      Some(ControlsAction::Rebind(action)) => {
+        state.rebinding = true;
+        state.controls_message = format!("Press a key for {}", action.name());
+      }
+      Some(ControlsAction::Done) => state.game_state = state.controls_back,
+      None => {}
+    }
+
+    input.clear();
+    update_controls_text(input, state);
+  }
+}
+
+impl ControlsSystem {
+  fn rebind(&self, input: &mut Input, state: &mut State, name: &str) {
+    state.rebinding = false;
+    let action = match state.controls_menu.menu.focused() {
+      Some(ControlsAction::Rebind(action)) => action,
+      _ => return,
+    };
+
+    match input.bindings.bind(action, state.controls_slot, name) {
+      Ok(()) => {
+        state.controls_message = format!("{} is now {}", action.name(), name);
+        if let Some(path) = &state.bindings_path {
+          if let Err(error) = input.bindings.save(path) {
+            eprintln!("Couldn't save controls to {}: {}", path.display(), error);
+          }
+        }
+      }
+      Err(other) => {
+        state.controls_message = format!("{} is already used for {}", name, other.name());
+      }
+    }
+  }
+}
+
+/// Shows each action's keys in its item, with the focused slot in brackets.
+fn update_controls_text(input: &Input, state: &mut State) {
+  let menu = &mut state.controls_menu.menu;
+  let focus = menu.focused();
+  for action in Action::ALL.iter() {
+    let item = ControlsAction::Rebind(*action);
+    let mut label = format!("{:<8}", action.name());
+
+    let keys = input.bindings.keys(*action);
+    for slot in 0..SLOTS {
+      let key = keys.get(slot).map_or("-", String::as_str);
+      if focus == Some(item) && slot == state.controls_slot {
+        label.push_str(&format!(" [{:^8}]", key));
+      } else {
+        label.push_str(&format!("  {:^8} ", key));
+      }
+    }
+    menu.set_label(item, label);
+  }
+
+  state.controls_text.render_text.text = state.controls_message.clone();
 }
@@ -0,0 +1,84 @@
+use std::time::Instant;
+
+/// Most ticks run for a single frame; anything beyond this is dropped so a
+/// long stall doesn't fast-forward the game.
+const MAX_TICKS_PER_UPDATE: u32 = 5;
+
+/// Converts frame time into a whole number of fixed-length simulation ticks.
+pub struct Ticker {
+  accumulator: f32,
+  last_time: Instant,
+}
+
+impl Default for Ticker {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Ticker {
+  pub fn new() -> Self {
+    Self {
+      accumulator: 0.0,
+      last_time: Instant::now(),
+    }
+  }
+
+  pub fn reset(&mut self) {
+    self.accumulator = 0.0;
+    self.last_time = Instant::now();
+  }
+
+  /// Measures the time since the last call and returns how many ticks to run.
+  pub fn update(&mut self, tick_rate: f32) -> u32 {
+    let current_time = Instant::now();
+    let delta_time = current_time - self.last_time;
+    self.last_time = current_time;
+
+    self.advance(delta_time.as_secs_f32(), tick_rate)
+  }
+
+  /// Adds `delta_time` seconds and returns how many ticks to run.
+  pub fn advance(&mut self, delta_time: f32, tick_rate: f32) -> u32 {
+    let tick_length = 1.0 / tick_rate;
+    self.accumulator += delta_time;
+
+    let owed = (self.accumulator / tick_length).floor();
+    self.accumulator %= tick_length;
+
+    (owed as u32).min(MAX_TICKS_PER_UPDATE)
+  }
+
+  /// How far into the next tick we are, from 0 to 1, for interpolating rendering.
+  pub fn alpha(&self, tick_rate: f32) -> f32 {
+    (self.accumulator * tick_rate).min(1.0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn ticks_are_owed_for_each_whole_tick_length() {
+    let mut ticker = Ticker::new();
+    assert_eq!(ticker.advance(0.05, 10.0), 0);
+    assert_eq!(ticker.advance(0.06, 10.0), 1);
+    assert!((ticker.alpha(10.0) - 0.1).abs() < 1e-4);
+    assert_eq!(ticker.advance(0.2, 10.0), 2);
+  }
+
+  #[test]
+  fn a_long_stall_runs_a_few_ticks_and_drops_the_rest() {
+    let mut ticker = Ticker::new();
+    assert_eq!(ticker.advance(10.0, 10.0), MAX_TICKS_PER_UPDATE);
+    assert_eq!(ticker.advance(0.0, 10.0), 0);
+  }
+
+  #[test]
+  fn a_huge_tick_rate_still_returns() {
+    let mut ticker = Ticker::new();
+    assert_eq!(ticker.advance(0.016, 1e10), MAX_TICKS_PER_UPDATE);
+    assert!(ticker.alpha(1e10) <= 1.0);
+  }
+}
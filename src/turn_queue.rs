@@ -0,0 +1,113 @@
+use crate::util::Direction;
+use std::collections::VecDeque;
+
+/// Turns buffered beyond this are dropped.
+const MAX_QUEUED_TURNS: usize = 3;
+
+/// Directions pressed during play, oldest first, taken one per tick so quick
+/// presses between ticks aren't lost.
+#[derive(Debug, Default)]
+pub struct TurnQueue {
+  turns: VecDeque<Direction>,
+}
+
+impl TurnQueue {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  pub fn push(&mut self, direction: Direction) {
+    if self.turns.back() == Some(&direction) {
+      return;
+    }
+
+    if self.turns.len() < MAX_QUEUED_TURNS {
+      self.turns.push_back(direction);
+    }
+  }
+
+  /// The next buffered turn the snake can take after travelling in
+  /// `travelled` last tick. Turns that would reverse the snake into itself,
+  /// or keep it going the way it already is, are discarded.
+  pub fn next_turn(&mut self, travelled: Direction) -> Option<Direction> {
+    while let Some(direction) = self.turns.pop_front() {
+      if direction != travelled && direction != travelled.opposite() {
+        return Some(direction);
+      }
+    }
+
+    None
+  }
+
+  pub fn clear(&mut self) {
+    self.turns.clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn turns_come_out_one_at_a_time_in_order() {
+    let mut queue = TurnQueue::new();
+    queue.push(Direction::Up);
+    queue.push(Direction::Left);
+
+    assert_eq!(queue.next_turn(Direction::Right), Some(Direction::Up));
+    assert_eq!(queue.next_turn(Direction::Up), Some(Direction::Left));
+    assert_eq!(queue.next_turn(Direction::Left), None);
+  }
+
+  #[test]
+  fn a_repeated_press_is_queued_once() {
+    let mut queue = TurnQueue::new();
+    queue.push(Direction::Up);
+    queue.push(Direction::Up);
+    queue.push(Direction::Left);
+
+    assert_eq!(queue.next_turn(Direction::Right), Some(Direction::Up));
+    assert_eq!(queue.next_turn(Direction::Up), Some(Direction::Left));
+    assert_eq!(queue.next_turn(Direction::Left), None);
+  }
+
+  #[test]
+  fn reversing_or_going_straight_on_is_skipped() {
+    let mut queue = TurnQueue::new();
+    queue.push(Direction::Left);
+    queue.push(Direction::Right);
+    queue.push(Direction::Up);
+
+    assert_eq!(queue.next_turn(Direction::Right), Some(Direction::Up));
+    assert_eq!(queue.next_turn(Direction::Up), None);
+  }
+
+  #[test]
+  fn no_more_than_three_turns_are_kept() {
+    let mut queue = TurnQueue::new();
+    for direction in [
+      Direction::Up,
+      Direction::Left,
+      Direction::Down,
+      Direction::Right,
+    ]
+    .iter()
+    {
+      queue.push(*direction);
+    }
+
+    assert_eq!(queue.next_turn(Direction::Right), Some(Direction::Up));
+    assert_eq!(queue.next_turn(Direction::Up), Some(Direction::Left));
+    assert_eq!(queue.next_turn(Direction::Left), Some(Direction::Down));
+    assert_eq!(queue.next_turn(Direction::Down), None);
+  }
+
+  #[test]
+  fn clearing_drops_every_turn() {
+    let mut queue = TurnQueue::new();
+    queue.push(Direction::Up);
+    queue.clear();
+
+    assert_eq!(queue.next_turn(Direction::Right), None);
+  }
+}
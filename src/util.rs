@@ -1,8 +1,8 @@
 #![macro_use]
 
-pub const STARTING_SNAKE_SPEED: f32 = 0.01;
-pub const SNAKE_SPEED_INC: f32 = 0.00075;
+use crate::board::Cell;
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Direction {
   None,
   Up,
@@ -11,6 +11,50 @@ pub enum Direction {
   Right,
 }
 
+impl Direction {
+  pub fn offset(&self) -> Cell {
+    match self {
+      Direction::None => (0, 0),
+      Direction::Up => (0, 1),
+      Direction::Down => (0, -1),
+      Direction::Left => (-1, 0),
+      Direction::Right => (1, 0),
+    }
+  }
+
+  pub fn opposite(&self) -> Direction {
+    match self {
+      Direction::None => Direction::None,
+      Direction::Up => Direction::Down,
+      Direction::Down => Direction::Up,
+      Direction::Left => Direction::Right,
+      Direction::Right => Direction::Left,
+    }
+  }
+
+  /// One-letter name used in replays and over the network, `-` for `None`.
+  pub fn letter(&self) -> char {
+    match self {
+      Direction::None => '-',
+      Direction::Up => 'U',
+      Direction::Down => 'D',
+      Direction::Left => 'L',
+      Direction::Right => 'R',
+    }
+  }
+
+  pub fn from_letter(letter: char) -> Option<Direction> {
+    match letter {
+      '-' => Some(Direction::None),
+      'U' => Some(Direction::Up),
+      'D' => Some(Direction::Down),
+      'L' => Some(Direction::Left),
+      'R' => Some(Direction::Right),
+      _ => None,
+    }
+  }
+}
+
 #[macro_export]
 macro_rules! any {
     ($x:expr, $($y:expr),+ $(,)?) => {